@@ -0,0 +1,63 @@
+use crate::{byte_span_to_range, source_uri};
+use diagnostic::{Diagnostic, SourceCache, Suggestion};
+use lsp_types::{CodeAction, CodeActionKind, PositionEncodingKind, TextEdit, Url, WorkspaceEdit};
+use std::collections::HashMap;
+
+/// What the `data` field of a published diagnostic holds, to find the [`Diagnostic`] it came from when a code action
+/// is requested.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DiagnosticData {
+    /// The position of the diagnostic among the diagnostics that were published together
+    pub index: usize,
+    /// The document whose check gave the diagnostic, if it is not the document the diagnostic is shown in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document: Option<Url>,
+    /// The version of the checked document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
+}
+
+impl DiagnosticData {
+    /// Read the data of a published diagnostic, `None` if it has none or it was not published by this crate.
+    pub fn from_lsp(diagnostic: &lsp_types::Diagnostic) -> Option<Self> {
+        serde_json::from_value(diagnostic.data.clone()?).ok()
+    }
+    /// Write the data into the `data` field of a diagnostic.
+    pub fn write_lsp(&self, diagnostic: &mut lsp_types::Diagnostic) {
+        diagnostic.data = serde_json::to_value(self).ok();
+    }
+}
+
+/// Convert a [`Suggestion`] to the edit that applies it, `None` if its source has no URI or its span is out of range.
+pub fn suggestion_edit(cache: &SourceCache, suggestion: &Suggestion, encoding: &PositionEncodingKind) -> Option<WorkspaceEdit> {
+    let span = cache.resolve_embedded(suggestion.get_span());
+    let uri = source_uri(cache, &span.file)?;
+    let range = byte_span_to_range(cache, &span, encoding).ok()?;
+    let edit = TextEdit { range, new_text: suggestion.get_replacement().to_string() };
+    Some(WorkspaceEdit { changes: Some(HashMap::from([(uri, vec![edit])])), ..Default::default() })
+}
+
+/// Get the quick fixes for a published diagnostic, one per suggestion of the [`Diagnostic`] it was converted from.
+///
+/// A diagnostic with a single suggestion marks it as preferred, so editors can apply it without asking.
+pub fn code_actions(
+    cache: &SourceCache,
+    diagnostic: &Diagnostic,
+    published: &lsp_types::Diagnostic,
+    encoding: &PositionEncodingKind,
+) -> Vec<CodeAction> {
+    let suggestions = diagnostic.get_suggestions();
+    suggestions
+        .iter()
+        .filter_map(|suggestion| {
+            Some(CodeAction {
+                title: suggestion.get_title(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![published.clone()]),
+                edit: Some(suggestion_edit(cache, suggestion, encoding)?),
+                is_preferred: Some(suggestions.len() == 1),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
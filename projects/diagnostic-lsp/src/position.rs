@@ -0,0 +1,201 @@
+use diagnostic::{SourceCache, SourceID, SourceSpan};
+use lsp_types::{ClientCapabilities, Position, PositionEncodingKind, Range};
+use source_cache::{LineEnding, SourceLine, SourceText};
+use std::io::{Error, ErrorKind};
+
+/// Pick the position encoding to use with a client, the first one it prefers, or UTF-16 if it names none.
+///
+/// UTF-8, UTF-16 and UTF-32 are supported, the server announces the result in
+/// [`ServerCapabilities::position_encoding`](lsp_types::ServerCapabilities::position_encoding).
+pub fn negotiate_encoding(client: &ClientCapabilities) -> PositionEncodingKind {
+    let supported = [PositionEncodingKind::UTF8, PositionEncodingKind::UTF16, PositionEncodingKind::UTF32];
+    let preferred = client.general.as_ref().and_then(|g| g.position_encodings.as_ref());
+    preferred.into_iter().flatten().find(|e| supported.contains(e)).cloned().unwrap_or(PositionEncodingKind::UTF16)
+}
+
+/// Count the code units of a character in the encoding, unknown encodings count UTF-16 code units.
+fn code_units(c: char, encoding: &PositionEncodingKind) -> u32 {
+    if *encoding == PositionEncodingKind::UTF8 {
+        c.len_utf8() as u32
+    }
+    else if *encoding == PositionEncodingKind::UTF32 {
+        1
+    }
+    else {
+        c.len_utf16() as u32
+    }
+}
+
+/// Get the text of a line as LSP counts it, up to its terminator.
+fn line_text<'a>(source: &'a SourceText, line: &SourceLine) -> &'a str {
+    &source.text()[line.offset as usize..(line.offset + line.length - line.ending.len()) as usize]
+}
+
+/// Check whether the text is empty or ends with a terminator, LSP then counts an empty last line that starts at the
+/// end of the text, which the [`SourceText`] has no [`SourceLine`] for.
+fn has_empty_last_line(source: &SourceText) -> bool {
+    let last = source.get_line_count().checked_sub(1).and_then(|last| source.get_line(last));
+    !matches!(last, Some(line) if line.ending.is_empty())
+}
+
+/// Convert a byte offset in a source to an LSP [`Position`], whose `character` counts code units of the negotiated
+/// encoding, see [`negotiate_encoding`].
+///
+/// Fails if the source is not in the cache, or the offset is past the end of the text or inside a character.
+pub fn byte_index_to_position(
+    files: &SourceCache,
+    file_id: &SourceID,
+    byte_index: u32,
+    encoding: &PositionEncodingKind,
+) -> Result<Position, Error> {
+    offset_to_position(files.fetch(file_id)?, byte_index, encoding)
+}
+
+/// Convert a byte offset in a text to an LSP [`Position`], see [`byte_index_to_position`].
+///
+/// The end of a text that is empty or ends with a line terminator is the start of the empty line after it, and offsets
+/// inside a terminator are placed at the end of their line.
+pub fn offset_to_position(source: &SourceText, byte_index: u32, encoding: &PositionEncodingKind) -> Result<Position, Error> {
+    if byte_index as usize == source.get_length() && has_empty_last_line(source) {
+        return Ok(Position { line: source.get_line_count() as u32, character: 0 });
+    }
+    let (line, line_index, column) = match source.get_offset_line(byte_index) {
+        Some(s) => s,
+        None => {
+            let message =
+                format!("Byte index {} is past the end of the text, which has {} bytes", byte_index, source.get_length());
+            return Err(Error::new(ErrorKind::InvalidInput, message));
+        }
+    };
+    // Offsets inside the terminator are placed at the end of the line
+    let line_str = line_text(source, line);
+    let column = (column as usize).min(line_str.len());
+    if !line_str.is_char_boundary(column) {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("Byte index {} is not on a character boundary", byte_index)));
+    }
+    let character = line_str[..column].chars().map(|c| code_units(c, encoding)).sum();
+    Ok(Position { line: line_index as u32, character })
+}
+
+/// Convert a [`SourceSpan`] to an LSP [`Range`] in the file the span belongs to, see [`byte_index_to_position`].
+pub fn byte_span_to_range(files: &SourceCache, span: &SourceSpan, encoding: &PositionEncodingKind) -> Result<Range, Error> {
+    Ok(Range {
+        start: byte_index_to_position(files, &span.file, span.start, encoding)?,
+        end: byte_index_to_position(files, &span.file, span.end, encoding)?,
+    })
+}
+
+/// Find the byte offset of a character in a line, a character past the end of the line means its end, like LSP asks.
+fn character_to_line_offset(line: &str, character: u32, encoding: &PositionEncodingKind) -> Result<u32, Error> {
+    let mut character_offset = 0;
+    for (index, ch) in line.char_indices() {
+        if character_offset == character {
+            return Ok(index as u32);
+        }
+        character_offset += code_units(ch, encoding);
+        if character_offset > character {
+            let message = format!("Character {} is in the middle of the character at byte {}", character, index);
+            return Err(Error::new(ErrorKind::InvalidInput, message));
+        }
+    }
+    // Characters past the end of the line are clamped to it
+    Ok(line.len() as u32)
+}
+
+/// Convert an LSP [`Position`] to a byte offset in a source, the inverse of [`byte_index_to_position`].
+///
+/// A character past the end of the line is the end of the line, a text that is empty or ends with a line terminator has
+/// an empty line after it. Fails if the source is not in the cache, the line does not exist, or the character is in the
+/// middle of a character.
+pub fn position_to_byte_index(
+    files: &SourceCache,
+    file_id: &SourceID,
+    position: &Position,
+    encoding: &PositionEncodingKind,
+) -> Result<u32, Error> {
+    position_to_offset(files.fetch(file_id)?, position, encoding)
+}
+
+/// Convert an LSP [`Position`] to a byte offset in a text, see [`position_to_byte_index`].
+pub fn position_to_offset(source: &SourceText, position: &Position, encoding: &PositionEncodingKind) -> Result<u32, Error> {
+    let line = match source.get_line(position.line as usize) {
+        Some(s) => s,
+        None if position.line as usize == source.get_line_count() && has_empty_last_line(source) => {
+            return Ok(source.get_length() as u32);
+        }
+        None => {
+            let message =
+                format!("Line {} is past the end of the text, which has {} lines", position.line, source.get_line_count());
+            return Err(Error::new(ErrorKind::InvalidInput, message));
+        }
+    };
+    let byte_offset = character_to_line_offset(line_text(source, line), position.character, encoding)?;
+
+    Ok(line.offset + byte_offset)
+}
+
+/// Find the first line terminator at or after `index`, returns where it starts and which it is.
+///
+/// Recognizes the same terminators as [`SourceText`], so positions agree with the ones of its lines.
+fn next_line_ending(text: &str, index: usize) -> Option<(usize, LineEnding)> {
+    let bytes = text.as_bytes();
+    // Every terminator starts with one of these bytes, which are never inside another UTF-8 sequence
+    (index..bytes.len())
+        .find_map(|i| match bytes[i] {
+            b'\n' | b'\r' | b'\x0B' | b'\x0C' | 0xC2 | 0xE2 => Some((i, LineEnding::from_prefix(&text[i..]))),
+            _ => None,
+        })
+        .filter(|(_, ending)| !ending.is_empty())
+}
+
+/// Find the start of `target` by skipping lines from the start of `line` at `offset`, which comes before it.
+fn scan_line_start(text: &str, mut line: u32, mut offset: usize, target: u32) -> Result<usize, Error> {
+    while line < target {
+        let Some((at, ending)) = next_line_ending(text, offset)
+        else {
+            let message = format!("Line {} is past the end of the text, which has {} lines", target, line + 1);
+            return Err(Error::new(ErrorKind::InvalidInput, message));
+        };
+        offset = at + ending.len() as usize;
+        line += 1;
+    }
+    Ok(offset)
+}
+
+/// Convert an LSP [`Range`] in a text that is being edited to a byte range, the same as [`position_to_offset`] gives
+/// for both ends, but only the lines up to the end of the range are scanned instead of indexing the whole text.
+///
+/// Fails like [`position_to_offset`], or if the range ends before it starts.
+pub(crate) fn text_range_to_offsets(
+    text: &str,
+    range: &Range,
+    encoding: &PositionEncodingKind,
+) -> Result<std::ops::Range<usize>, Error> {
+    let offset_in_line = |line_start: usize, character: u32| -> Result<usize, Error> {
+        let line_end = next_line_ending(text, line_start).map_or(text.len(), |(at, _)| at);
+        Ok(line_start + character_to_line_offset(&text[line_start..line_end], character, encoding)? as usize)
+    };
+    let start_line = scan_line_start(text, 0, 0, range.start.line)?;
+    let end_line = match range.end.line >= range.start.line {
+        true => scan_line_start(text, range.start.line, start_line, range.end.line)?,
+        false => scan_line_start(text, 0, 0, range.end.line)?,
+    };
+    let (start, end) = (offset_in_line(start_line, range.start.character)?, offset_in_line(end_line, range.end.character)?);
+    if start > end {
+        let message = format!("Range starts at {:?}, after its end {:?}", range.start, range.end);
+        return Err(Error::new(ErrorKind::InvalidInput, message));
+    }
+    Ok(start..end)
+}
+
+/// Convert an LSP [`Range`] to a [`SourceSpan`] in a source, the inverse of [`byte_span_to_range`].
+pub fn range_to_byte_span(
+    files: &SourceCache,
+    file_id: &SourceID,
+    range: &Range,
+    encoding: &PositionEncodingKind,
+) -> Result<SourceSpan, Error> {
+    let start = position_to_byte_index(files, file_id, &range.start, encoding)?;
+    let end = position_to_byte_index(files, file_id, &range.end, encoding)?;
+    Ok(SourceSpan::new(*file_id, start, end))
+}
@@ -0,0 +1,137 @@
+use crate::{byte_span_to_range, DiagnosticData};
+use diagnostic::{Diagnostic, ReportLevel, SourceCache, SourceID, SourceSpan};
+use lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, PositionEncodingKind, PublishDiagnosticsParams,
+    Url,
+};
+use source_cache::SourcePath;
+
+/// Get the document URI of a source, `file://` for local files and the URL itself for remote sources.
+///
+/// Embedded sources resolve to the source they are embedded in. Other sources, like snippets and files inside
+/// archives, have no document an editor could open and give `None`.
+pub fn source_uri(cache: &SourceCache, file: &SourceID) -> Option<Url> {
+    match cache.source_path(file)? {
+        SourcePath::Local(path) => Url::from_file_path(std::path::absolute(path).ok()?).ok(),
+        SourcePath::Remote(url) => Some(url.clone()),
+        SourcePath::Embedded(region) if region.file != *file => source_uri(cache, &region.file),
+        _ => None,
+    }
+}
+
+/// Get the [`SourceID`] of the document at a URI, the inverse of [`source_uri`].
+///
+/// `file://` URIs are local files, so a file loaded by [`SourceCache::load_local`] is found by its URI, also when it
+/// was loaded by a path relative to the working directory. Other URIs are remote sources.
+pub fn uri_source_id(cache: &SourceCache, uri: &Url) -> SourceID {
+    let Ok(path) = uri.to_file_path()
+    else {
+        return SourcePath::Remote(uri.clone()).source_id();
+    };
+    let file = SourcePath::Local(path.clone()).source_id();
+    if cache.source_path(&file).is_some() {
+        return file;
+    }
+    // `source_uri` makes relative paths absolute, the cache knows the file by the relative path
+    let relative = std::env::current_dir().ok().and_then(|dir| Some(path.strip_prefix(dir).ok()?.to_path_buf()));
+    match relative.map(|path| SourcePath::Local(path).source_id()) {
+        Some(relative) if cache.source_path(&relative).is_some() => relative,
+        _ => file,
+    }
+}
+
+/// Get the LSP severity of a report by its level, [`ReportKind::Error`](diagnostic::ReportKind::Error) and above are
+/// errors, [`ReportKind::Alert`](diagnostic::ReportKind::Alert) and above warnings,
+/// [`ReportKind::Blame`](diagnostic::ReportKind::Blame) and above information and the rest hints.
+pub fn diagnostic_severity(kind: &dyn ReportLevel) -> DiagnosticSeverity {
+    match kind.level() {
+        250.. => DiagnosticSeverity::ERROR,
+        200.. => DiagnosticSeverity::WARNING,
+        150.. => DiagnosticSeverity::INFORMATION,
+        _ => DiagnosticSeverity::HINT,
+    }
+}
+
+/// Convert a [`Diagnostic`] to an LSP diagnostic, returns it with the URI of the document it belongs to.
+///
+/// The diagnostic is placed on the first label in the file of its location, or at the location if no label is there,
+/// the other labels become its related information. The note and help are appended to the message. Gives `None` if the
+/// file has no URI, see [`source_uri`], or the span the diagnostic is placed on is outside of it. Positions count code units of the encoding, see
+/// [`negotiate_encoding`](crate::negotiate_encoding).
+pub fn to_lsp_diagnostic(
+    cache: &SourceCache,
+    diagnostic: &Diagnostic,
+    encoding: &PositionEncodingKind,
+) -> Option<(Url, lsp_types::Diagnostic)> {
+    let (file, location) = diagnostic.get_location();
+    let labels: Vec<_> = diagnostic.get_labels().iter().map(|l| (cache.resolve_embedded(l.get_span()), l)).collect();
+    let at = cache.resolve_embedded(SourceSpan::new(file, location.unwrap_or(0), location.unwrap_or(0)));
+    let primary = labels.iter().position(|(span, _)| span.file == at.file);
+    let primary_span = primary.map_or(&at, |index| &labels[index].0);
+    let uri = source_uri(cache, &primary_span.file)?;
+    let range = byte_span_to_range(cache, primary_span, encoding).ok()?;
+    let mut related = vec![];
+    for (index, (span, label)) in labels.iter().enumerate() {
+        if Some(index) == primary {
+            continue;
+        }
+        let (Some(uri), Ok(range)) = (source_uri(cache, &span.file), byte_span_to_range(cache, span, encoding))
+        else {
+            continue;
+        };
+        let message = label.get_message().unwrap_or_default().to_string();
+        related.push(DiagnosticRelatedInformation { location: Location { uri, range }, message });
+    }
+    let mut message = diagnostic.get_message().to_string();
+    if let Some(help) = diagnostic.get_help() {
+        message.push_str(&format!("\nHelp: {}", help));
+    }
+    if let Some(note) = diagnostic.get_note() {
+        message.push_str(&format!("\nNote: {}", note));
+    }
+    let lsp = lsp_types::Diagnostic {
+        range,
+        severity: Some(diagnostic_severity(diagnostic.get_kind())),
+        code: diagnostic.get_code().map(lsp_code),
+        message,
+        related_information: if related.is_empty() { None } else { Some(related) },
+        ..Default::default()
+    };
+    Some((uri, lsp))
+}
+
+/// Get the LSP code of a diagnostic code, codes that do not fit in the protocol's integers are sent as strings.
+fn lsp_code(code: usize) -> NumberOrString {
+    match i32::try_from(code) {
+        Ok(code) => NumberOrString::Number(code),
+        Err(_) => NumberOrString::String(code.to_string()),
+    }
+}
+
+/// Convert diagnostics to the notifications that publish them, one per document in the order documents are first
+/// seen, see [`to_lsp_diagnostic`].
+///
+/// Diagnostics in sources that have no URI, or placed outside of their source, are left out. Each diagnostic carries its position in `diagnostics` in its
+/// `data` field, see [`DiagnosticData`].
+pub fn publish_diagnostics<'a, I>(
+    cache: &SourceCache,
+    diagnostics: I,
+    encoding: &PositionEncodingKind,
+) -> Vec<PublishDiagnosticsParams>
+where
+    I: IntoIterator<Item = &'a Diagnostic>,
+{
+    let mut params: Vec<PublishDiagnosticsParams> = vec![];
+    for (index, diagnostic) in diagnostics.into_iter().enumerate() {
+        let Some((uri, mut diagnostic)) = to_lsp_diagnostic(cache, diagnostic, encoding)
+        else {
+            continue;
+        };
+        DiagnosticData { index, document: None, version: None }.write_lsp(&mut diagnostic);
+        match params.iter_mut().find(|p| p.uri == uri) {
+            Some(p) => p.diagnostics.push(diagnostic),
+            None => params.push(PublishDiagnosticsParams::new(uri, vec![diagnostic], None)),
+        }
+    }
+    params
+}
@@ -0,0 +1,76 @@
+use crate::{range_to_byte_span, uri_source_id};
+use diagnostic::{Diagnostic, Label, ReportKind, SourceCache, SourceID};
+use lsp_types::{DiagnosticSeverity, NumberOrString, PositionEncodingKind, PublishDiagnosticsParams};
+use std::io::Error;
+
+/// Get the report kind of an LSP severity, diagnostics without a severity are errors.
+pub fn report_kind(severity: Option<DiagnosticSeverity>) -> ReportKind {
+    match severity {
+        Some(DiagnosticSeverity::WARNING) => ReportKind::Alert,
+        Some(DiagnosticSeverity::INFORMATION) => ReportKind::Blame,
+        Some(DiagnosticSeverity::HINT) => ReportKind::Trace,
+        _ => ReportKind::Error,
+    }
+}
+
+/// Convert an LSP diagnostic in a document to a [`Diagnostic`] that can be printed, the inverse of
+/// [`to_lsp_diagnostic`](crate::to_lsp_diagnostic).
+///
+/// The text of the document must be in the cache, the range of the diagnostic becomes its first label and each related
+/// location whose document is in the cache another label, see [`uri_source_id`]. Numeric codes become the code of the
+/// report, other codes and the name of the source that produced the diagnostic are put before the message.
+///
+/// ```
+/// # use diagnostic::SourceCache;
+/// # use diagnostic_lsp::{from_lsp_diagnostic, lsp_types::*, uri_source_id};
+/// let mut cache = SourceCache::default();
+/// let file = cache.open_buffer("/project/main.x", "let x = y;").unwrap();
+/// let lsp = Diagnostic {
+///     range: Range::new(Position::new(0, 8), Position::new(0, 9)),
+///     message: "Unknown variable `y`".to_string(),
+///     ..Default::default()
+/// };
+/// let report = from_lsp_diagnostic(&cache, file, &lsp, &PositionEncodingKind::UTF16).unwrap();
+/// report.eprint(&cache).unwrap();
+/// ```
+pub fn from_lsp_diagnostic(
+    cache: &SourceCache,
+    file: SourceID,
+    diagnostic: &lsp_types::Diagnostic,
+    encoding: &PositionEncodingKind,
+) -> Result<Diagnostic, Error> {
+    let span = range_to_byte_span(cache, &file, &diagnostic.range, encoding)?;
+    let mut message = diagnostic.message.clone();
+    let mut builder = Diagnostic::new(report_kind(diagnostic.severity)).with_location(file, Some(span.start));
+    match &diagnostic.code {
+        Some(NumberOrString::Number(code)) if *code >= 0 => builder.set_code(Some(*code as usize)),
+        Some(NumberOrString::Number(code)) => message = format!("[{}] {}", code, message),
+        Some(NumberOrString::String(code)) => message = format!("[{}] {}", code, message),
+        None => {}
+    }
+    if let Some(source) = &diagnostic.source {
+        message = format!("{}: {}", source, message);
+    }
+    builder.set_message(message);
+    builder.add_label(Label::new(span));
+    for related in diagnostic.related_information.iter().flatten() {
+        let related_file = uri_source_id(cache, &related.location.uri);
+        if let Ok(span) = range_to_byte_span(cache, &related_file, &related.location.range, encoding) {
+            builder.add_label(Label::new(span).with_message(&related.message));
+        }
+    }
+    Ok(builder.finish())
+}
+
+/// Convert the diagnostics a server published for a document, see [`from_lsp_diagnostic`].
+///
+/// Returns one result per diagnostic in the same order, so a diagnostic whose range is outside of the document fails
+/// on its own and the caller decides whether it fails the run. All of them fail if the document is not in the cache.
+pub fn from_publish_diagnostics(
+    cache: &SourceCache,
+    params: &PublishDiagnosticsParams,
+    encoding: &PositionEncodingKind,
+) -> Vec<Result<Diagnostic, Error>> {
+    let file = uri_source_id(cache, &params.uri);
+    params.diagnostics.iter().map(|d| from_lsp_diagnostic(cache, file, d, encoding)).collect()
+}
@@ -0,0 +1,390 @@
+use crate::{code_actions, negotiate_encoding, position::text_range_to_offsets, publish_diagnostics, DiagnosticData};
+use diagnostic::{Diagnostic, SourceCache, SourceID};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, LogMessage, Notification as _, PublishDiagnostics,
+    },
+    request::{CodeActionRequest, DocumentDiagnosticRequest, Request as _, WorkspaceDiagnosticRequest},
+    CodeActionOrCommand, CodeActionParams, CodeActionProviderCapability, CodeActionResponse, DiagnosticOptions,
+    DiagnosticServerCapabilities, DocumentDiagnosticParams, InitializeParams, InitializeResult, LogMessageParams, MessageType,
+    PositionEncodingKind, PublishDiagnosticsParams, ServerCapabilities, ServerInfo, TextDocumentContentChangeEvent,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url, WorkspaceDiagnosticParams,
+};
+use source_cache::{SourcePath, SourceText};
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+};
+
+mod pull;
+
+/// A language server that keeps the open documents in a [`SourceCache`] and publishes the diagnostics of a checker.
+///
+/// Documents with `file://` URIs are opened as unsaved buffers of local files, see [`SourceCache::open_buffer`], so
+/// other files the checker loads see the edited text. Documents with other URIs are remote sources. Whenever a
+/// document is opened or changed, the checker runs on it and its diagnostics are published, with their
+/// [`Suggestion`](diagnostic::Suggestion)s offered as quick fixes.
+///
+/// ```no_run
+/// # use diagnostic::{Diagnostic, ReportKind, SourceCache, SourceID};
+/// # use diagnostic_lsp::LanguageServer;
+/// fn check(cache: &SourceCache, file: SourceID) -> Vec<Diagnostic> {
+///     let text = cache.fetch(&file).unwrap().text();
+///     match text.find("TODO") {
+///         Some(i) => vec![
+///             Diagnostic::new(ReportKind::Alert)
+///                 .with_location(file, Some(i as u32))
+///                 .with_message("TODO")
+///                 .finish(),
+///         ],
+///         None => vec![],
+///     }
+/// }
+///
+/// LanguageServer::new(check).with_name("todo-lsp").run_stdio().unwrap();
+/// ```
+pub struct LanguageServer<F> {
+    cache: SourceCache,
+    check: F,
+    name: Option<String>,
+    documents: HashMap<Url, (SourceID, i32)>,
+    /// The position encoding negotiated with the client
+    encoding: PositionEncodingKind,
+    /// The number of times a document was opened, changed or closed, checks that read other documents only hold for
+    /// the generation they ran in
+    generation: u64,
+    /// Whether checks read other open documents, see [`LanguageServer::with_inter_file_dependencies`]
+    dependencies: bool,
+    /// The diagnostics of the last check of each document, with the version checked and the generation it ran in
+    checked: HashMap<Url, (i32, u64, Vec<Diagnostic>)>,
+    /// Whether the client pulls diagnostics instead of having them published
+    pull: bool,
+    /// The diagnostics the last check of each open document put in each document, as they were last published
+    reports: HashMap<Url, Vec<PublishDiagnosticsParams>>,
+}
+
+impl<F> LanguageServer<F>
+where
+    F: FnMut(&SourceCache, SourceID) -> Vec<Diagnostic>,
+{
+    /// Create a new [`LanguageServer`] that runs `check` on every opened or changed document.
+    pub fn new(check: F) -> Self {
+        Self {
+            cache: SourceCache::default(),
+            check,
+            name: None,
+            documents: HashMap::new(),
+            encoding: PositionEncodingKind::UTF16,
+            generation: 0,
+            dependencies: false,
+            checked: HashMap::new(),
+            pull: false,
+            reports: HashMap::new(),
+        }
+    }
+    /// Start with the sources in the cache, like a loaded standard library.
+    pub fn with_cache(mut self, cache: SourceCache) -> Self {
+        self.cache = cache;
+        self
+    }
+    /// Set the name the server reports to the client.
+    pub fn with_name<S: ToString>(mut self, name: S) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+    /// Let clients that support it pull diagnostics with `textDocument/diagnostic` and `workspace/diagnostic` instead
+    /// of having them published on every change, other clients still have them published.
+    ///
+    /// Documents are only checked when their diagnostics are pulled, and a document whose version and text did not
+    /// change since the last pull is reported as unchanged.
+    pub fn with_pull_diagnostics(mut self, pull: bool) -> Self {
+        self.pull = pull;
+        self
+    }
+    /// Declare that the checker reads other open documents, so the diagnostics of a document can change when another
+    /// one does.
+    ///
+    /// Pulled diagnostics are then reported as unchanged only while no document was opened, changed or closed, and
+    /// clients are told to pull again for all documents when one changes. Off by default, so a keystroke only gets the
+    /// edited document checked again.
+    pub fn with_inter_file_dependencies(mut self, dependencies: bool) -> Self {
+        self.dependencies = dependencies;
+        self
+    }
+    /// Get the cache holding the documents.
+    pub fn get_cache(&self) -> &SourceCache {
+        &self.cache
+    }
+    /// Get the source of an open document, and the version the client gave it.
+    pub fn get_document(&self, uri: &Url) -> Option<(SourceID, i32)> {
+        self.documents.get(uri).copied()
+    }
+    /// Get the position encoding negotiated with the client, UTF-16 until the client is initialized.
+    pub fn get_encoding(&self) -> &PositionEncodingKind {
+        &self.encoding
+    }
+    /// Serve a client on stdin and stdout until it shuts the server down.
+    pub fn run_stdio(mut self) -> Result<(), Error> {
+        let (connection, io_threads) = Connection::stdio();
+        self.run(&connection)?;
+        drop(connection);
+        io_threads.join()
+    }
+    /// Serve a client on the connection until it shuts the server down, [`Connection::memory`] gives a connection to
+    /// test a server with.
+    pub fn run(&mut self, connection: &Connection) -> Result<(), Error> {
+        let (id, params) = connection.initialize_start().map_err(protocol_error)?;
+        let params: InitializeParams = serde_json::from_value(params).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        self.encoding = negotiate_encoding(&params.capabilities);
+        let text_document = params.capabilities.text_document.as_ref();
+        self.pull = self.pull && text_document.and_then(|t| t.diagnostic.as_ref()).is_some();
+        let diagnostic_provider = self.pull.then(|| {
+            DiagnosticServerCapabilities::Options(DiagnosticOptions {
+                identifier: self.name.clone(),
+                inter_file_dependencies: self.dependencies,
+                workspace_diagnostics: true,
+                ..Default::default()
+            })
+        });
+        let capabilities = ServerCapabilities {
+            position_encoding: Some(self.encoding.clone()),
+            text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::INCREMENTAL)),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+            diagnostic_provider,
+            ..Default::default()
+        };
+        let server_info = self.name.clone().map(|name| ServerInfo { name, version: None });
+        let result = InitializeResult { capabilities, server_info };
+        connection.initialize_finish(id, to_value(result)?).map_err(protocol_error)?;
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request).map_err(protocol_error)? {
+                        return Ok(());
+                    }
+                    let response = self.on_request(request);
+                    send(connection, Message::Response(response))?;
+                }
+                Message::Notification(notification) => match self.on_notification(notification) {
+                    Ok(published) => {
+                        for params in published {
+                            let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+                            send(connection, Message::Notification(notification))?;
+                        }
+                    }
+                    // A bad notification leaves the documents as they were, the session goes on
+                    Err(e) => {
+                        let params = LogMessageParams { typ: MessageType::ERROR, message: e.to_string() };
+                        let notification = Notification::new(LogMessage::METHOD.to_string(), params);
+                        send(connection, Message::Notification(notification))?;
+                    }
+                },
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+    fn on_request(&mut self, request: Request) -> Response {
+        let id = request.id.clone();
+        match request.method.as_str() {
+            CodeActionRequest::METHOD => match request.extract::<CodeActionParams>(CodeActionRequest::METHOD) {
+                Ok((_, params)) => Response::new_ok(id, self.code_actions(params)),
+                Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+            },
+            DocumentDiagnosticRequest::METHOD if self.pull => {
+                match request.extract::<DocumentDiagnosticParams>(DocumentDiagnosticRequest::METHOD) {
+                    Ok((_, params)) => Response::new_ok(id, self.document_diagnostic(params)),
+                    Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+                }
+            }
+            WorkspaceDiagnosticRequest::METHOD if self.pull => {
+                match request.extract::<WorkspaceDiagnosticParams>(WorkspaceDiagnosticRequest::METHOD) {
+                    Ok((_, params)) => Response::new_ok(id, self.workspace_diagnostic(params)),
+                    Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+                }
+            }
+            _ => {
+                let message = format!("Unsupported request: {}", request.method);
+                Response::new_err(request.id, ErrorCode::MethodNotFound as i32, message)
+            }
+        }
+    }
+    /// Find the suggestions of the diagnostics in the request by their data, diagnostics of an older version of the
+    /// checked document have none.
+    fn code_actions(&self, params: CodeActionParams) -> CodeActionResponse {
+        let mut actions = vec![];
+        for published in &params.context.diagnostics {
+            let Some(data) = DiagnosticData::from_lsp(published)
+            else {
+                continue;
+            };
+            let document = data.document.as_ref().unwrap_or(&params.text_document.uri);
+            let diagnostic = match self.checked.get(document) {
+                Some((version, _, diagnostics)) if data.version.is_none() || data.version == Some(*version) => {
+                    diagnostics.get(data.index)
+                }
+                _ => None,
+            };
+            if let Some(diagnostic) = diagnostic {
+                actions.extend(
+                    code_actions(&self.cache, diagnostic, published, &self.encoding).into_iter().map(CodeActionOrCommand::from),
+                );
+            }
+        }
+        actions
+    }
+    /// Apply a text synchronization notification, returns the diagnostics to publish.
+    fn on_notification(&mut self, notification: Notification) -> Result<Vec<PublishDiagnosticsParams>, Error> {
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = extract::<DidOpenTextDocument>(notification)?;
+                let document = params.text_document;
+                self.update(&document.uri, document.text, document.version)?;
+                document.uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = extract::<DidChangeTextDocument>(notification)?;
+                let document = params.text_document;
+                let Some((file, _)) = self.get_document(&document.uri)
+                else {
+                    return Err(Error::new(ErrorKind::NotFound, format!("{} is not open", document.uri)));
+                };
+                let text = self.apply_changes(file, params.content_changes)?;
+                self.update(&document.uri, text, document.version)?;
+                document.uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = extract::<DidCloseTextDocument>(notification)?;
+                let uri = params.text_document.uri;
+                self.checked.remove(&uri);
+                if self.documents.remove(&uri).is_some() {
+                    self.generation += 1;
+                    if let Ok(path) = uri.to_file_path() {
+                        // The file may not exist on disk, the buffer is gone either way
+                        let _ = self.cache.close_buffer(path);
+                    }
+                }
+                uri
+            }
+            _ => return Ok(vec![]),
+        };
+        match self.pull {
+            true => Ok(vec![]),
+            false => Ok(self.publish(&uri)),
+        }
+    }
+    /// Store the new text of a document.
+    fn update(&mut self, uri: &Url, text: String, version: i32) -> Result<SourceID, Error> {
+        let file = match uri.to_file_path() {
+            Ok(path) => self.cache.open_buffer(path, text)?,
+            Err(_) => {
+                let mut source = SourceText::from(text);
+                source.set_source(SourcePath::Remote(uri.clone()));
+                self.cache.insert(source)?
+            }
+        };
+        self.documents.insert(uri.clone(), (file, version));
+        self.generation += 1;
+        Ok(file)
+    }
+    /// Get the text of a document after the changes, in order, fails if a range ends before it starts.
+    fn apply_changes(&mut self, file: SourceID, changes: Vec<TextDocumentContentChangeEvent>) -> Result<String, Error> {
+        let mut text = self.cache.fetch(&file)?.text().to_string();
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    // Positions refer to the text after the previous changes
+                    let offsets = text_range_to_offsets(&text, &range, &self.encoding)?;
+                    text.replace_range(offsets, &change.text);
+                }
+                None => text = change.text,
+            }
+        }
+        Ok(text)
+    }
+    /// Check a document if it is open, returns the diagnostics to publish for every document the check reports into now
+    /// or did before.
+    ///
+    /// Publishing replaces all diagnostics of a document, so each one gets the diagnostics of every open document's
+    /// check merged, its own first, and a document no check reports into any more gets an empty set.
+    fn publish(&mut self, uri: &Url) -> Vec<PublishDiagnosticsParams> {
+        let previous = match self.get_document(uri) {
+            Some(_) => {
+                let reports = self.check_document(uri);
+                self.reports.insert(uri.clone(), reports)
+            }
+            None => self.reports.remove(uri),
+        };
+        let mut documents = vec![uri.clone()];
+        for report in previous.iter().chain(self.reports.get(uri)).flatten() {
+            if !documents.contains(&report.uri) {
+                documents.push(report.uri.clone());
+            }
+        }
+        documents.into_iter().map(|document| self.merged_report(document)).collect()
+    }
+    /// Merge the diagnostics the checks of all open documents put in a document.
+    fn merged_report(&self, uri: Url) -> PublishDiagnosticsParams {
+        let mut checked: Vec<_> = self.reports.keys().collect();
+        checked.sort_by_key(|checked| (**checked != uri, *checked));
+        let reports = checked.into_iter().flat_map(|checked| &self.reports[checked]).filter(|report| report.uri == uri);
+        let diagnostics = reports.flat_map(|report| report.diagnostics.iter().cloned()).collect();
+        let version = self.get_document(&uri).map(|(_, version)| version);
+        PublishDiagnosticsParams::new(uri, diagnostics, version)
+    }
+    /// Run the checker on a document unless this version was checked, the document itself is always in the result so
+    /// fixed diagnostics are cleared.
+    fn check_document(&mut self, uri: &Url) -> Vec<PublishDiagnosticsParams> {
+        let Some((file, version)) = self.get_document(uri)
+        else {
+            return vec![];
+        };
+        let diagnostics = match self.checked.remove(uri) {
+            Some((checked, generation, diagnostics))
+                if checked == version && (!self.dependencies || generation == self.generation) =>
+            {
+                diagnostics
+            }
+            _ => (self.check)(&self.cache, file),
+        };
+        let mut params = publish_diagnostics(&self.cache, &diagnostics, &self.encoding);
+        for p in &mut params {
+            for published in &mut p.diagnostics {
+                let Some(mut data) = DiagnosticData::from_lsp(published)
+                else {
+                    continue;
+                };
+                data.document = if p.uri == *uri { None } else { Some(uri.clone()) };
+                data.version = Some(version);
+                data.write_lsp(published);
+            }
+        }
+        match params.iter_mut().find(|p| p.uri == *uri) {
+            Some(p) => p.version = Some(version),
+            None => params.insert(0, PublishDiagnosticsParams::new(uri.clone(), vec![], Some(version))),
+        }
+        self.checked.insert(uri.clone(), (version, self.generation, diagnostics));
+        params
+    }
+}
+
+fn extract<N>(notification: Notification) -> Result<N::Params, Error>
+where
+    N: lsp_types::notification::Notification,
+{
+    notification.extract(N::METHOD).map_err(|e| Error::new(ErrorKind::InvalidData, format!("{:?}", e)))
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> Result<serde_json::Value, Error> {
+    serde_json::to_value(value).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+fn send(connection: &Connection, message: Message) -> Result<(), Error> {
+    connection.sender.send(message).map_err(|e| Error::new(ErrorKind::BrokenPipe, e.to_string()))
+}
+
+fn protocol_error(error: lsp_server::ProtocolError) -> Error {
+    let kind = if error.channel_is_disconnected() { ErrorKind::BrokenPipe } else { ErrorKind::InvalidData };
+    Error::new(kind, error)
+}
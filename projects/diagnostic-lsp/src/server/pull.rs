@@ -0,0 +1,86 @@
+use super::*;
+use lsp_types::{
+    DocumentDiagnosticReport, DocumentDiagnosticReportKind, DocumentDiagnosticReportResult, FullDocumentDiagnosticReport,
+    RelatedFullDocumentDiagnosticReport, RelatedUnchangedDocumentDiagnosticReport, UnchangedDocumentDiagnosticReport,
+    WorkspaceDiagnosticReport, WorkspaceDiagnosticReportResult, WorkspaceDocumentDiagnosticReport,
+    WorkspaceFullDocumentDiagnosticReport, WorkspaceUnchangedDocumentDiagnosticReport,
+};
+
+impl<F> LanguageServer<F>
+where
+    F: FnMut(&SourceCache, SourceID) -> Vec<Diagnostic>,
+{
+    /// Get the result id of the diagnostics of an open document, its version and the hash of its text.
+    ///
+    /// The hash tells apart texts with the same version, as versions start over when a document is opened again. With
+    /// inter-file dependencies the generation of the documents comes first, as it changes whenever any document does.
+    fn result_id(&self, uri: &Url) -> Option<String> {
+        let (file, version) = self.get_document(uri)?;
+        let source = self.cache.fetch(&file).ok()?;
+        let id = format!("{}-{:016x}", version, source.get_content_hash());
+        match self.dependencies {
+            true => Some(format!("{}-{}", self.generation, id)),
+            false => Some(id),
+        }
+    }
+    /// Answer `textDocument/diagnostic`, diagnostics the check of the document puts in other documents are reported as
+    /// related documents.
+    pub(super) fn document_diagnostic(&mut self, params: DocumentDiagnosticParams) -> DocumentDiagnosticReportResult {
+        let uri = params.text_document.uri;
+        let result_id = self.result_id(&uri);
+        if let (Some(previous), Some(result_id)) = (params.previous_result_id, &result_id) {
+            if previous == *result_id {
+                let unchanged = UnchangedDocumentDiagnosticReport { result_id: previous };
+                let report = RelatedUnchangedDocumentDiagnosticReport {
+                    related_documents: None,
+                    unchanged_document_diagnostic_report: unchanged,
+                };
+                return DocumentDiagnosticReport::Unchanged(report).into();
+            }
+        }
+        let mut items = vec![];
+        let mut related = HashMap::new();
+        for published in self.check_document(&uri) {
+            if published.uri == uri {
+                items = published.diagnostics;
+            }
+            else {
+                let report = FullDocumentDiagnosticReport { result_id: None, items: published.diagnostics };
+                related.insert(published.uri, DocumentDiagnosticReportKind::Full(report));
+            }
+        }
+        let report = RelatedFullDocumentDiagnosticReport {
+            related_documents: if related.is_empty() { None } else { Some(related) },
+            full_document_diagnostic_report: FullDocumentDiagnosticReport { result_id, items },
+        };
+        DocumentDiagnosticReport::Full(report).into()
+    }
+    /// Answer `workspace/diagnostic` with a report for every open document, in the order of their URIs.
+    ///
+    /// Each document only reports the diagnostics its own check puts in it.
+    pub(super) fn workspace_diagnostic(&mut self, params: WorkspaceDiagnosticParams) -> WorkspaceDiagnosticReportResult {
+        let previous: HashMap<Url, String> = params.previous_result_ids.into_iter().map(|p| (p.uri, p.value)).collect();
+        let mut documents: Vec<_> = self.documents.iter().map(|(uri, (_, version))| (uri.clone(), *version)).collect();
+        documents.sort();
+        let mut items = vec![];
+        for (uri, version) in documents {
+            let result_id = self.result_id(&uri);
+            let version = Some(version as i64);
+            if result_id.is_some() && previous.get(&uri) == result_id.as_ref() {
+                let unchanged = UnchangedDocumentDiagnosticReport { result_id: result_id.unwrap_or_default() };
+                let report = WorkspaceUnchangedDocumentDiagnosticReport {
+                    uri,
+                    version,
+                    unchanged_document_diagnostic_report: unchanged,
+                };
+                items.push(WorkspaceDocumentDiagnosticReport::Unchanged(report));
+                continue;
+            }
+            let published = self.check_document(&uri).into_iter().find(|p| p.uri == uri);
+            let full = FullDocumentDiagnosticReport { result_id, items: published.map(|p| p.diagnostics).unwrap_or_default() };
+            let report = WorkspaceFullDocumentDiagnosticReport { uri, version, full_document_diagnostic_report: full };
+            items.push(WorkspaceDocumentDiagnosticReport::Full(report));
+        }
+        WorkspaceDiagnosticReport { items }.into()
+    }
+}
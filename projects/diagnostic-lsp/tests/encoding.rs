@@ -0,0 +1,72 @@
+use diagnostic_lsp::{negotiate_encoding, offset_to_position, position_to_offset};
+use lsp_types::{ClientCapabilities, GeneralClientCapabilities, Position, PositionEncodingKind};
+use source_cache::SourceText;
+
+const ENCODINGS: [PositionEncodingKind; 3] =
+    [PositionEncodingKind::UTF8, PositionEncodingKind::UTF16, PositionEncodingKind::UTF32];
+
+/// BMP, astral plane, a ZWJ sequence of astral characters, and astral characters at both ends of the line.
+const LINES: [&str; 4] = ["åä t𐐀b", "x👩\u{200D}💻y", "𐐀𐐀", "\u{10FFFF}中\u{1F600}"];
+
+fn units(c: char, encoding: &PositionEncodingKind) -> u32 {
+    match encoding.as_str() {
+        "utf-8" => c.len_utf8() as u32,
+        "utf-16" => c.len_utf16() as u32,
+        _ => 1,
+    }
+}
+
+#[test]
+fn astral_round_trip() {
+    let text = LINES.join("\n");
+    let source = SourceText::from(text.clone());
+    for encoding in &ENCODINGS {
+        let mut offset = 0;
+        for (line, s) in LINES.iter().enumerate() {
+            let mut character = 0;
+            for (index, c) in s.char_indices().chain([(s.len(), '\n')]) {
+                let position = Position::new(line as u32, character);
+                let at = (offset + index) as u32;
+                assert_eq!(offset_to_position(&source, at, encoding).unwrap(), position, "{:?} at {}", encoding, at);
+                assert_eq!(position_to_offset(&source, &position, encoding).unwrap(), at, "{:?} {:?}", encoding, position);
+                // Offsets inside the character, and code units inside it, are not positions
+                for inside in 1..c.len_utf8() as u32 {
+                    assert!(offset_to_position(&source, at + inside, encoding).is_err());
+                }
+                for inside in 1..units(c, encoding) {
+                    let position = Position::new(line as u32, character + inside);
+                    assert!(position_to_offset(&source, &position, encoding).is_err(), "{:?} {:?}", encoding, position);
+                }
+                character += units(c, encoding);
+            }
+            offset += s.len() + 1;
+        }
+    }
+}
+
+#[test]
+fn astral_columns() {
+    // `𐐀` is 4 bytes, 2 UTF-16 code units and 1 code point
+    let source = SourceText::from("t𐐀b");
+    let columns: Vec<_> = ENCODINGS.iter().map(|e| offset_to_position(&source, 5, e).unwrap().character).collect();
+    assert_eq!(columns, vec![5, 3, 2]);
+    let offsets: Vec<_> = ENCODINGS.iter().map(|e| position_to_offset(&source, &Position::new(0, 2), e)).collect();
+    assert!(offsets[0].is_err());
+    assert!(offsets[1].is_err());
+    assert_eq!(offsets[2].as_ref().unwrap(), &5);
+}
+
+#[test]
+fn negotiation() {
+    let client = |encodings: Option<Vec<PositionEncodingKind>>| ClientCapabilities {
+        general: Some(GeneralClientCapabilities { position_encodings: encodings, ..Default::default() }),
+        ..Default::default()
+    };
+    assert_eq!(negotiate_encoding(&ClientCapabilities::default()), PositionEncodingKind::UTF16);
+    assert_eq!(negotiate_encoding(&client(None)), PositionEncodingKind::UTF16);
+    assert_eq!(negotiate_encoding(&client(Some(vec!["utf-7".to_string().into()]))), PositionEncodingKind::UTF16);
+    let preferred = vec!["utf-7".to_string().into(), PositionEncodingKind::UTF8, PositionEncodingKind::UTF16];
+    assert_eq!(negotiate_encoding(&client(Some(preferred))), PositionEncodingKind::UTF8);
+    let preferred = vec![PositionEncodingKind::UTF32, PositionEncodingKind::UTF8];
+    assert_eq!(negotiate_encoding(&client(Some(preferred))), PositionEncodingKind::UTF32);
+}
@@ -1,7 +1,9 @@
-use lsp_types::Position;
+use lsp_types::{Position, PositionEncodingKind, Range};
 
 use diagnostic::{SourceCache, SourceSpan};
-use diagnostic_lsp::{byte_index_to_position, position_to_byte_index};
+use diagnostic_lsp::{byte_index_to_position, byte_span_to_range, position_to_byte_index, range_to_byte_span};
+
+const UTF16: PositionEncodingKind = PositionEncodingKind::UTF16;
 
 const TEST_TEXT: &str = r#"
 let test = 2
@@ -10,17 +12,59 @@ test
 "#;
 #[test]
 fn position() {
-    let mut files = SourceSpan::default();
-    let file_id = files.anonymous(TEST_TEXT);
-    let pos = position_to_byte_index(&files, &file_id, &Position { line: 3, character: 2 }).unwrap();
-    assert_eq!(
-        Location {
-            // One-based
-            line_number: 3 + 1,
-            column_number: 2 + 1,
-        },
-        files.location(&file_id, pos).unwrap()
-    );
+    let mut files = SourceCache::default();
+    let file_id = files.load_text(TEST_TEXT, "test.x").unwrap();
+    let pos = position_to_byte_index(&files, &file_id, &Position { line: 3, character: 2 }, &UTF16).unwrap();
+    let (_, line, column) = files.fetch(&file_id).unwrap().get_offset_line(pos).unwrap();
+    // Zero-based
+    assert_eq!((line, column), (3, 2));
+}
+
+#[test]
+fn out_of_range() {
+    let mut files = SourceCache::default();
+    let file_id = files.load_text(TEST_TEXT, "test.x").unwrap();
+    assert!(position_to_byte_index(&files, &file_id, &Position { line: 9, character: 0 }, &UTF16).is_err());
+    assert!(byte_index_to_position(&files, &file_id, 100, &UTF16).is_err());
+    // Characters past the end of a line are clamped to it
+    let pos = position_to_byte_index(&files, &file_id, &Position { line: 1, character: 20 }, &UTF16).unwrap();
+    assert_eq!(&TEST_TEXT[..pos as usize], "\nlet test = 2");
+}
+
+#[test]
+fn empty_document() {
+    let mut files = SourceCache::default();
+    let file_id = files.load_text("", "empty.x").unwrap();
+    assert_eq!(position_to_byte_index(&files, &file_id, &Position { line: 0, character: 0 }, &UTF16).unwrap(), 0);
+    assert_eq!(position_to_byte_index(&files, &file_id, &Position { line: 0, character: 4 }, &UTF16).unwrap(), 0);
+    assert!(position_to_byte_index(&files, &file_id, &Position { line: 1, character: 0 }, &UTF16).is_err());
+    assert_eq!(byte_index_to_position(&files, &file_id, 0, &UTF16).unwrap(), Position { line: 0, character: 0 });
+}
+
+#[test]
+fn end_of_file() {
+    let mut files = SourceCache::default();
+    let file_id = files.load_text("a\n", "newline.x").unwrap();
+    // The end of the text is the start of the empty line after the terminator
+    assert_eq!(byte_index_to_position(&files, &file_id, 2, &UTF16).unwrap(), Position { line: 1, character: 0 });
+    assert_eq!(position_to_byte_index(&files, &file_id, &Position { line: 1, character: 0 }, &UTF16).unwrap(), 2);
+    assert_eq!(position_to_byte_index(&files, &file_id, &Position { line: 0, character: 5 }, &UTF16).unwrap(), 1);
+    assert!(position_to_byte_index(&files, &file_id, &Position { line: 2, character: 0 }, &UTF16).is_err());
+
+    let file_id = files.load_text("a", "no-newline.x").unwrap();
+    assert_eq!(byte_index_to_position(&files, &file_id, 1, &UTF16).unwrap(), Position { line: 0, character: 1 });
+    assert!(position_to_byte_index(&files, &file_id, &Position { line: 1, character: 0 }, &UTF16).is_err());
+}
+
+#[test]
+fn crlf() {
+    let mut files = SourceCache::default();
+    let file_id = files.load_text("a\r\nb", "crlf.x").unwrap();
+    // Characters are counted up to the terminator, never between `\r` and `\n`
+    assert_eq!(position_to_byte_index(&files, &file_id, &Position { line: 0, character: 2 }, &UTF16).unwrap(), 1);
+    assert_eq!(byte_index_to_position(&files, &file_id, 2, &UTF16).unwrap(), Position { line: 0, character: 1 });
+    assert_eq!(byte_index_to_position(&files, &file_id, 3, &UTF16).unwrap(), Position { line: 1, character: 0 });
+    assert_eq!(position_to_byte_index(&files, &file_id, &Position { line: 1, character: 1 }, &UTF16).unwrap(), 4);
 }
 
 // The protocol specifies that each `character` in position is a UTF-16 character.
@@ -30,27 +74,40 @@ const UNICODE: &str = "åä t𐐀b";
 #[test]
 fn unicode_get_byte_index() {
     let mut files = SourceCache::default();
-    let file_id = files.anonymous(UNICODE);
+    let file_id = files.load_text(UNICODE, "unicode.x").unwrap();
 
-    let result = position_to_byte_index(&files, &file_id, &Position { line: 0, character: 3 });
+    let result = position_to_byte_index(&files, &file_id, &Position { line: 0, character: 3 }, &UTF16);
     assert_eq!(result.unwrap(), 5);
 
-    let result = position_to_byte_index(&files, &file_id, &Position { line: 0, character: 6 });
+    let result = position_to_byte_index(&files, &file_id, &Position { line: 0, character: 6 }, &UTF16);
     assert_eq!(result.unwrap(), 10);
 }
 
 #[test]
 fn unicode_get_position() {
     let mut files = SourceCache::default();
-    let file_id = files.anonymous(UNICODE.to_string());
-    let file_id2 = files.anonymous("\n".to_string() + UNICODE);
+    let file_id = files.load_text(UNICODE, "unicode.x").unwrap();
+    let file_id2 = files.load_text("\n".to_string() + UNICODE, "unicode2.x").unwrap();
 
-    let result = byte_index_to_position(&files, &file_id, 5);
+    let result = byte_index_to_position(&files, &file_id, 5, &UTF16);
     assert_eq!(result.unwrap(), Position { line: 0, character: 3 });
 
-    let result = byte_index_to_position(&files, &file_id, 10);
+    let result = byte_index_to_position(&files, &file_id, 10, &UTF16);
     assert_eq!(result.unwrap(), Position { line: 0, character: 6 });
 
-    let result = byte_index_to_position(&files, &file_id2, 11);
+    let result = byte_index_to_position(&files, &file_id2, 11, &UTF16);
     assert_eq!(result.unwrap(), Position { line: 1, character: 6 });
+
+    // Inside the four bytes of `𐐀`
+    assert!(byte_index_to_position(&files, &file_id, 7, &UTF16).is_err());
+}
+
+#[test]
+fn unicode_range() {
+    let mut files = SourceCache::default();
+    let file_id = files.load_text("\n".to_string() + UNICODE, "unicode.x").unwrap();
+    let span = SourceSpan::new(file_id, 6, 11);
+    let range = byte_span_to_range(&files, &span, &UTF16).unwrap();
+    assert_eq!(range, Range { start: Position { line: 1, character: 3 }, end: Position { line: 1, character: 6 } });
+    assert_eq!(range_to_byte_span(&files, &file_id, &range, &UTF16).unwrap(), span);
 }
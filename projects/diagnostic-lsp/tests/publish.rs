@@ -0,0 +1,112 @@
+use diagnostic::{Diagnostic, Label, ReportKind, SourceCache, SourceID};
+use diagnostic_lsp::{publish_diagnostics, source_uri, to_lsp_diagnostic, uri_source_id};
+use lsp_types::{DiagnosticSeverity, NumberOrString, Position, PositionEncodingKind, Range, Url};
+use source_cache::{SourcePath, SourceText};
+
+fn insert(cache: &mut SourceCache, path: SourcePath, text: &str) -> SourceID {
+    let mut source = SourceText::from(text.to_string());
+    source.set_source(path);
+    cache.insert(source).unwrap()
+}
+
+fn range(line: u32, start: u32, end: u32) -> Range {
+    Range { start: Position { line, character: start }, end: Position { line, character: end } }
+}
+
+#[test]
+fn group_by_document() {
+    let mut cache = SourceCache::default();
+    let main = insert(&mut cache, SourcePath::Local("/project/main.x".into()), "import lib\nlet x = lib.y\n");
+    let lib_url = Url::parse("https://example.com/lib.x").unwrap();
+    let lib = insert(&mut cache, SourcePath::Remote(lib_url.clone()), "let z = 1\n");
+    let snippet = cache.load_text("let w = 2", "repl").unwrap();
+
+    let diagnostics = vec![
+        Diagnostic::new(ReportKind::Error)
+            .with_code(3)
+            .with_location(main, Some(19))
+            .with_message("`lib` has no member `y`")
+            .with_label(Label::new(lib.with_range(4..5)).with_message("Did you mean `z`?"))
+            .with_label(Label::new(main.with_range(23..24)).with_message("Unknown member"))
+            .with_help("Rename `y` to `z`")
+            .finish(),
+        Diagnostic::new(ReportKind::Alert).with_location(lib, Some(4)).with_message("Unused `z`").finish(),
+        Diagnostic::new(ReportKind::Trace).with_location(main, Some(0)).with_message("Imports go first").finish(),
+        Diagnostic::new(ReportKind::Error).with_location(snippet, Some(0)).with_message("Not a document").finish(),
+    ];
+    let params = publish_diagnostics(&cache, &diagnostics, &PositionEncodingKind::UTF16);
+    assert_eq!(params.len(), 2);
+
+    let main_url = source_uri(&cache, &main).unwrap();
+    assert_eq!(main_url.as_str(), "file:///project/main.x");
+    assert_eq!(params[0].uri, main_url);
+    assert_eq!(params[0].diagnostics.len(), 2);
+    let error = &params[0].diagnostics[0];
+    assert_eq!(error.range, range(1, 12, 13));
+    assert_eq!(error.severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(error.code, Some(NumberOrString::Number(3)));
+    assert_eq!(error.message, "`lib` has no member `y`\nHelp: Rename `y` to `z`");
+    let related = error.related_information.as_ref().unwrap();
+    assert_eq!(related.len(), 1);
+    assert_eq!(related[0].location.uri, lib_url);
+    assert_eq!(related[0].location.range, range(0, 4, 5));
+    assert_eq!(related[0].message, "Did you mean `z`?");
+    assert_eq!(params[0].diagnostics[1].severity, Some(DiagnosticSeverity::HINT));
+    assert_eq!(params[0].diagnostics[1].range, range(0, 0, 0));
+
+    assert_eq!(params[1].uri, lib_url);
+    assert_eq!(params[1].diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    assert_eq!(params[1].diagnostics[0].related_information, None);
+}
+
+#[test]
+fn embedded_in_document() {
+    let mut cache = SourceCache::default();
+    let page = insert(&mut cache, SourcePath::Local("/project/page.html".into()), "<p>\n<script>let a = ;</script>\n");
+    let script = cache.add_embedded(page.with_range(12..21)).unwrap();
+    let diagnostics = [Diagnostic::new(ReportKind::Error)
+        .with_location(script, Some(8))
+        .with_label(Label::new(script.with_range(8..9)).with_message("Expected an expression"))
+        .finish()];
+    let params = publish_diagnostics(&cache, &diagnostics, &PositionEncodingKind::UTF16);
+    assert_eq!(params[0].uri.as_str(), "file:///project/page.html");
+    assert_eq!(params[0].diagnostics[0].range, range(1, 16, 17));
+}
+
+#[test]
+fn large_codes() {
+    let mut cache = SourceCache::default();
+    let main = insert(&mut cache, SourcePath::Local("/project/main.x".into()), "let x = 1\n");
+    let large = i32::MAX as usize + 1;
+    let diagnostics = [i32::MAX as usize, large].map(|code| {
+        Diagnostic::new(ReportKind::Error).with_code(code).with_location(main, Some(0)).with_message("Bad").finish()
+    });
+    let params = publish_diagnostics(&cache, &diagnostics, &PositionEncodingKind::UTF16);
+    assert_eq!(params[0].diagnostics[0].code, Some(NumberOrString::Number(i32::MAX)));
+    assert_eq!(params[0].diagnostics[1].code, Some(NumberOrString::String(large.to_string())));
+}
+
+#[test]
+fn primary_span_outside_of_source() {
+    let mut cache = SourceCache::default();
+    let main = insert(&mut cache, SourcePath::Local("/project/main.x".into()), "let x = 1\n");
+    let outside = Diagnostic::new(ReportKind::Error).with_location(main, Some(50)).with_message("Stale").finish();
+    assert!(to_lsp_diagnostic(&cache, &outside, &PositionEncodingKind::UTF16).is_none());
+    let labelled = Diagnostic::new(ReportKind::Error)
+        .with_location(main, Some(0))
+        .with_label(Label::new(main.with_range(40..45)))
+        .with_message("Stale")
+        .finish();
+    assert!(publish_diagnostics(&cache, [&outside, &labelled], &PositionEncodingKind::UTF16).is_empty());
+}
+
+#[test]
+fn relative_paths_round_trip() {
+    let mut cache = SourceCache::default();
+    let relative = insert(&mut cache, SourcePath::Local("src/relative.x".into()), "let x = 1\n");
+    let uri = source_uri(&cache, &relative).unwrap();
+    assert!(uri.path().ends_with("/src/relative.x"), "{}", uri);
+    assert_eq!(uri_source_id(&cache, &uri), relative);
+    let absolute = insert(&mut cache, SourcePath::Local("/project/absolute.x".into()), "");
+    assert_eq!(uri_source_id(&cache, &source_uri(&cache, &absolute).unwrap()), absolute);
+}
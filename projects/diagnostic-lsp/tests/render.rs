@@ -0,0 +1,80 @@
+use diagnostic::SourceCache;
+use diagnostic_lsp::{from_publish_diagnostics, report_kind};
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Position, PositionEncodingKind,
+    PublishDiagnosticsParams, Range, Url,
+};
+
+fn range(line: u32, start: u32, end: u32) -> Range {
+    Range::new(Position::new(line, start), Position::new(line, end))
+}
+
+#[test]
+fn render_published() {
+    let mut cache = SourceCache::default();
+    cache.open_buffer("/project/main.x", "import lib\nlet 𐐀 = lib.y\n").unwrap();
+    cache.open_buffer("/project/lib.x", "let z = 1\n").unwrap();
+    let lib = Url::parse("file:///project/lib.x").unwrap();
+    let unknown = Url::parse("file:///project/unknown.x").unwrap();
+
+    let error = Diagnostic {
+        range: range(1, 13, 14),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::Number(3)),
+        source: Some("xc".to_string()),
+        message: "`lib` has no member `y`".to_string(),
+        related_information: Some(vec![
+            DiagnosticRelatedInformation {
+                location: Location::new(lib, range(0, 4, 5)),
+                message: "Did you mean `z`?".to_string(),
+            },
+            DiagnosticRelatedInformation {
+                location: Location::new(unknown, range(0, 0, 1)),
+                message: "Not loaded".to_string(),
+            },
+        ]),
+        ..Default::default()
+    };
+    let hint = Diagnostic {
+        range: range(0, 0, 6),
+        severity: Some(DiagnosticSeverity::HINT),
+        code: Some(NumberOrString::String("style".to_string())),
+        message: "Imports go first".to_string(),
+        ..Default::default()
+    };
+    let params = PublishDiagnosticsParams::new(Url::parse("file:///project/main.x").unwrap(), vec![error, hint], None);
+    let reports: Vec<_> =
+        from_publish_diagnostics(&cache, &params, &PositionEncodingKind::UTF16).into_iter().map(Result::unwrap).collect();
+    assert_eq!(reports.len(), 2);
+
+    let report = &reports[0];
+    assert_eq!(report.get_code(), Some(3));
+    assert_eq!(report.get_message(), "xc: `lib` has no member `y`");
+    let spans: Vec<_> = report.get_labels().iter().map(|l| l.get_span().get_range()).collect();
+    assert_eq!(spans, vec![26..27, 4..5]);
+    assert_eq!(reports[1].get_message(), "[style] Imports go first");
+    assert_eq!(reports[1].get_kind().level(), diagnostic::ReportLevel::level(&report_kind(Some(DiagnosticSeverity::HINT))));
+
+    let mut out = Vec::new();
+    report.write(&cache, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("ERROR[0003]:"), "{}", out);
+    assert!(out.contains(" xc: `lib` has no member `y`"), "{}", out);
+    assert!(out.contains("/project/main.x:2:16"), "{}", out);
+    assert!(out.contains("Did you mean `z`?"), "{}", out);
+
+    // A range outside the document only fails that diagnostic
+    let outside = Diagnostic { range: range(9, 0, 0), ..Default::default() };
+    let inside = Diagnostic { range: range(0, 0, 6), message: "Kept".to_string(), ..Default::default() };
+    let params = PublishDiagnosticsParams::new(params.uri, vec![outside.clone(), inside], None);
+    let results = from_publish_diagnostics(&cache, &params, &PositionEncodingKind::UTF16);
+    let [Err(_), Ok(kept)] = results.as_slice()
+    else {
+        panic!("expected only the first diagnostic to fail");
+    };
+    assert_eq!(kept.get_message(), "Kept");
+
+    let params = PublishDiagnosticsParams::new(Url::parse("file:///project/unknown.x").unwrap(), vec![outside], None);
+    let results = from_publish_diagnostics(&cache, &params, &PositionEncodingKind::UTF16);
+    assert_eq!(results[0].as_ref().unwrap_err().kind(), std::io::ErrorKind::NotFound);
+}
@@ -0,0 +1,419 @@
+use diagnostic::{Diagnostic, Label, ReportKind, SourceCache, SourceID, Suggestion};
+use diagnostic_lsp::{
+    lsp_server::{Connection, Message, Notification, Request, RequestId},
+    LanguageServer,
+};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized, LogMessage, Notification as _, PublishDiagnostics,
+    },
+    request::{CodeActionRequest, DocumentDiagnosticRequest, Initialize, Shutdown, WorkspaceDiagnosticRequest},
+    CodeActionContext, CodeActionKind, CodeActionOrCommand, CodeActionParams, DiagnosticServerCapabilities,
+    DidChangeTextDocumentParams, DidOpenTextDocumentParams, DocumentDiagnosticParams, DocumentDiagnosticReport,
+    DocumentDiagnosticReportResult, GeneralClientCapabilities, InitializeParams, InitializeResult, InitializedParams,
+    LogMessageParams, MessageType, Position, PositionEncodingKind, PreviousResultId, PublishDiagnosticsParams, Range,
+    TextDocumentClientCapabilities, TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem, TextEdit, Url,
+    VersionedTextDocumentIdentifier, WorkspaceDiagnosticParams, WorkspaceDiagnosticReportResult,
+    WorkspaceDocumentDiagnosticReport,
+};
+use source_cache::SourcePath;
+use std::thread;
+
+/// Report every `TODO` in the document.
+fn check(cache: &SourceCache, file: SourceID) -> Vec<Diagnostic> {
+    let text = cache.fetch(&file).unwrap().text();
+    text.match_indices("TODO")
+        .map(|(i, _)| {
+            let i = i as u32;
+            Diagnostic::new(ReportKind::Alert)
+                .with_location(file, Some(i))
+                .with_message("Unfinished work")
+                .with_label(Label::new(file.with_range(i..i + 4)))
+                .with_suggestion(Suggestion::new(file.with_range(i..i + 4), "DONE"))
+                .finish()
+        })
+        .collect()
+}
+
+/// Report every `TODO`, and an `@other` in a document at the start of `untitled:other`.
+fn check_other(cache: &SourceCache, file: SourceID) -> Vec<Diagnostic> {
+    let mut diagnostics = check(cache, file);
+    if cache.fetch(&file).unwrap().text().contains("@other") {
+        let other = SourcePath::Remote(Url::parse("untitled:other").unwrap()).source_id();
+        diagnostics.push(Diagnostic::new(ReportKind::Error).with_location(other, Some(0)).with_message("Mentioned").finish());
+    }
+    diagnostics
+}
+
+fn notify<N: lsp_types::notification::Notification>(client: &Connection, params: N::Params) {
+    client.sender.send(Message::Notification(Notification::new(N::METHOD.to_string(), params))).unwrap();
+}
+
+fn published(client: &Connection) -> PublishDiagnosticsParams {
+    match client.receiver.recv().unwrap() {
+        Message::Notification(n) if n.method == PublishDiagnostics::METHOD => serde_json::from_value(n.params).unwrap(),
+        message => panic!("expected diagnostics, got {:?}", message),
+    }
+}
+
+fn request<R: lsp_types::request::Request>(client: &Connection, id: i32, params: R::Params) -> R::Result {
+    client.sender.send(Message::Request(Request::new(RequestId::from(id), R::METHOD.to_string(), params))).unwrap();
+    match client.receiver.recv().unwrap() {
+        Message::Response(r) if r.id == RequestId::from(id) => serde_json::from_value(r.result.unwrap()).unwrap(),
+        message => panic!("expected the response to {}, got {:?}", R::METHOD, message),
+    }
+}
+
+fn initialize(client: &Connection) -> InitializeResult {
+    initialize_with(client, InitializeParams::default())
+}
+
+fn initialize_with(client: &Connection, params: InitializeParams) -> InitializeResult {
+    let result = request::<Initialize>(client, 1, params);
+    notify::<Initialized>(client, InitializedParams {});
+    result
+}
+
+fn shutdown(client: &Connection, server: thread::JoinHandle<std::io::Result<()>>) {
+    request::<Shutdown>(client, 99, ());
+    notify::<Exit>(client, ());
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn publish_on_open_and_change() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(check).with_name("todo").run(&server));
+    assert_eq!(initialize(&client).server_info.unwrap().name, "todo");
+
+    let uri = Url::parse("file:///project/notes.txt").unwrap();
+    let text_document = TextDocumentItem::new(uri.clone(), "text".to_string(), 1, "a\nb TODO 𐐀 TODO\n".to_string());
+    notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    let params = published(&client);
+    assert_eq!(params.uri, uri);
+    assert_eq!(params.version, Some(1));
+    let ranges: Vec<_> = params.diagnostics.iter().map(|d| d.range).collect();
+    let at = |line, start, end| Range::new(Position::new(line, start), Position::new(line, end));
+    assert_eq!(ranges, vec![at(1, 2, 6), at(1, 10, 14)]);
+
+    // Replace the first `TODO`, then clear the document
+    let text_document = VersionedTextDocumentIdentifier::new(uri.clone(), 2);
+    let content_changes =
+        vec![TextDocumentContentChangeEvent { range: Some(at(1, 2, 6)), range_length: None, text: "done".to_string() }];
+    notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    let params = published(&client);
+    assert_eq!(params.version, Some(2));
+    assert_eq!(params.diagnostics.iter().map(|d| d.range).collect::<Vec<_>>(), vec![at(1, 10, 14)]);
+
+    let text_document = VersionedTextDocumentIdentifier::new(uri.clone(), 3);
+    let content_changes = vec![TextDocumentContentChangeEvent { range: None, range_length: None, text: "".to_string() }];
+    notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    assert!(published(&client).diagnostics.is_empty());
+
+    shutdown(&client, handle);
+}
+
+#[test]
+fn quick_fix() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(check).run(&server));
+    initialize(&client);
+
+    let uri = Url::parse("untitled:notes").unwrap();
+    let text_document = TextDocumentItem::new(uri.clone(), "text".to_string(), 1, "x TODO".to_string());
+    notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    let diagnostics = published(&client).diagnostics;
+
+    let params = CodeActionParams {
+        text_document: TextDocumentIdentifier::new(uri.clone()),
+        range: diagnostics[0].range,
+        context: CodeActionContext { diagnostics: diagnostics.clone(), only: None, trigger_kind: None },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let actions = request::<CodeActionRequest>(&client, 2, params.clone()).unwrap();
+    let [CodeActionOrCommand::CodeAction(action)] = actions.as_slice()
+    else {
+        panic!("expected one code action, got {:?}", actions);
+    };
+    assert_eq!(action.title, "Replace with `DONE`");
+    assert_eq!(action.kind, Some(CodeActionKind::QUICKFIX));
+    assert_eq!(action.is_preferred, Some(true));
+    let edits = &action.edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri];
+    let at = Range::new(Position::new(0, 2), Position::new(0, 6));
+    assert_eq!(edits, &vec![TextEdit::new(at, "DONE".to_string())]);
+
+    // The diagnostics of version 1 have no fixes once the document changed
+    let text_document = VersionedTextDocumentIdentifier::new(uri.clone(), 2);
+    let content_changes = vec![TextDocumentContentChangeEvent { range: None, range_length: None, text: "TODO".to_string() }];
+    notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    published(&client);
+    assert_eq!(request::<CodeActionRequest>(&client, 3, params), Some(vec![]));
+
+    shutdown(&client, handle);
+}
+
+#[test]
+fn utf8_positions() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(check).run(&server));
+    let mut params = InitializeParams::default();
+    params.capabilities.general = Some(GeneralClientCapabilities {
+        position_encodings: Some(vec![PositionEncodingKind::UTF8, PositionEncodingKind::UTF16]),
+        ..Default::default()
+    });
+    assert_eq!(initialize_with(&client, params).capabilities.position_encoding, Some(PositionEncodingKind::UTF8));
+
+    let uri = Url::parse("untitled:notes").unwrap();
+    let text_document = TextDocumentItem::new(uri.clone(), "text".to_string(), 1, "𐐀 TODO".to_string());
+    notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    let at = |start, end| Range::new(Position::new(0, start), Position::new(0, end));
+    assert_eq!(published(&client).diagnostics[0].range, at(5, 9));
+
+    // Edits count bytes as well
+    let text_document = VersionedTextDocumentIdentifier::new(uri.clone(), 2);
+    let content_changes =
+        vec![TextDocumentContentChangeEvent { range: Some(at(0, 4)), range_length: None, text: "ab".to_string() }];
+    notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    assert_eq!(published(&client).diagnostics[0].range, at(3, 7));
+
+    shutdown(&client, handle);
+}
+
+#[test]
+fn edits_at_end_of_file() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(check).run(&server));
+    initialize(&client);
+
+    let uri = Url::parse("untitled:notes").unwrap();
+    let text_document = TextDocumentItem::new(uri.clone(), "text".to_string(), 1, "".to_string());
+    notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    assert!(published(&client).diagnostics.is_empty());
+
+    // Type into the empty document, then on the empty line after its terminator
+    let at = |line, start, end| Range::new(Position::new(line, start), Position::new(line, end));
+    for (version, line, text) in [(2, 0, "TODO\n"), (3, 1, "TODO")] {
+        let text_document = VersionedTextDocumentIdentifier::new(uri.clone(), version);
+        let content_changes =
+            vec![TextDocumentContentChangeEvent { range: Some(at(line, 0, 0)), range_length: None, text: text.to_string() }];
+        notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    }
+    published(&client);
+    let ranges: Vec<_> = published(&client).diagnostics.iter().map(|d| d.range).collect();
+    assert_eq!(ranges, vec![at(0, 0, 4), at(1, 0, 4)]);
+
+    shutdown(&client, handle);
+}
+
+#[test]
+fn reversed_change_range() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(check).run(&server));
+    initialize(&client);
+
+    let uri = Url::parse("untitled:notes").unwrap();
+    let text_document = TextDocumentItem::new(uri.clone(), "text".to_string(), 1, "a TODO b".to_string());
+    notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    published(&client);
+
+    // The change is rejected, the server keeps running and the document is unchanged
+    let text_document = VersionedTextDocumentIdentifier::new(uri.clone(), 2);
+    let range = Range::new(Position::new(0, 6), Position::new(0, 2));
+    let content_changes = vec![TextDocumentContentChangeEvent { range: Some(range), range_length: None, text: "".to_string() }];
+    notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    let log: LogMessageParams = match client.receiver.recv().unwrap() {
+        Message::Notification(n) if n.method == LogMessage::METHOD => serde_json::from_value(n.params).unwrap(),
+        message => panic!("expected a log message, got {:?}", message),
+    };
+    assert_eq!(log.typ, MessageType::ERROR);
+
+    let text_document = VersionedTextDocumentIdentifier::new(uri.clone(), 3);
+    let range = Range::new(Position::new(0, 0), Position::new(0, 1));
+    let content_changes =
+        vec![TextDocumentContentChangeEvent { range: Some(range), range_length: None, text: "TODO".to_string() }];
+    notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    assert_eq!(published(&client).diagnostics.len(), 2);
+
+    shutdown(&client, handle);
+}
+
+#[test]
+fn merge_reports_into_other_documents() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(check_other).run(&server));
+    initialize(&client);
+    let messages = |params: PublishDiagnosticsParams| {
+        let messages: Vec<_> = params.diagnostics.into_iter().map(|d| d.message).collect();
+        (params.uri.to_string(), messages)
+    };
+    let change = |uri: &Url, version, text: &str| {
+        let text_document = VersionedTextDocumentIdentifier::new(uri.clone(), version);
+        let content_changes = vec![TextDocumentContentChangeEvent { range: None, range_length: None, text: text.to_string() }];
+        notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    };
+
+    let other = Url::parse("untitled:other").unwrap();
+    let text_document = TextDocumentItem::new(other.clone(), "text".to_string(), 1, "x".to_string());
+    notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    assert_eq!(messages(published(&client)), ("untitled:other".to_string(), vec![]));
+    let main = Url::parse("untitled:main").unwrap();
+    let text_document = TextDocumentItem::new(main.clone(), "text".to_string(), 1, "TODO @other".to_string());
+    notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    assert_eq!(messages(published(&client)), ("untitled:main".to_string(), vec!["Unfinished work".to_string()]));
+    assert_eq!(messages(published(&client)), ("untitled:other".to_string(), vec!["Mentioned".to_string()]));
+
+    // Checking `other` keeps what `main` reported there
+    change(&other, 2, "TODO");
+    let expected = vec!["Unfinished work".to_string(), "Mentioned".to_string()];
+    assert_eq!(messages(published(&client)), ("untitled:other".to_string(), expected));
+
+    // Once `main` stops reporting into `other`, only its own diagnostics are left there
+    change(&main, 2, "done");
+    assert_eq!(messages(published(&client)), ("untitled:main".to_string(), vec![]));
+    assert_eq!(messages(published(&client)), ("untitled:other".to_string(), vec!["Unfinished work".to_string()]));
+
+    shutdown(&client, handle);
+}
+
+fn pull(client: &Connection, id: i32, uri: &Url, previous_result_id: Option<String>) -> DocumentDiagnosticReport {
+    let params = DocumentDiagnosticParams {
+        text_document: TextDocumentIdentifier::new(uri.clone()),
+        identifier: None,
+        previous_result_id,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    match request::<DocumentDiagnosticRequest>(client, id, params) {
+        DocumentDiagnosticReportResult::Report(report) => report,
+        result => panic!("expected a report, got {:?}", result),
+    }
+}
+
+#[test]
+fn pull_diagnostics() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(check).with_pull_diagnostics(true).run(&server));
+    let mut params = InitializeParams::default();
+    params.capabilities.text_document =
+        Some(TextDocumentClientCapabilities { diagnostic: Some(Default::default()), ..Default::default() });
+    let provider = initialize_with(&client, params).capabilities.diagnostic_provider;
+    let Some(DiagnosticServerCapabilities::Options(options)) = provider
+    else {
+        panic!("expected diagnostic options, got {:?}", provider);
+    };
+    assert!(!options.inter_file_dependencies);
+
+    // Nothing is published, the first request answers with the diagnostics
+    let uri = Url::parse("untitled:notes").unwrap();
+    let text_document = TextDocumentItem::new(uri.clone(), "text".to_string(), 1, "TODO TODO".to_string());
+    notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    let other = Url::parse("untitled:other").unwrap();
+    let text_document = TextDocumentItem::new(other.clone(), "text".to_string(), 1, "".to_string());
+    notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    let DocumentDiagnosticReport::Full(report) = pull(&client, 2, &uri, None)
+    else {
+        panic!("expected a full report");
+    };
+    let full = report.full_document_diagnostic_report;
+    assert_eq!(full.items.len(), 2);
+    let first_id = full.result_id.unwrap();
+    let DocumentDiagnosticReport::Unchanged(report) = pull(&client, 3, &uri, Some(first_id.clone()))
+    else {
+        panic!("expected an unchanged report");
+    };
+    assert_eq!(report.unchanged_document_diagnostic_report.result_id, first_id);
+
+    let text_document = VersionedTextDocumentIdentifier::new(uri.clone(), 2);
+    let content_changes = vec![TextDocumentContentChangeEvent { range: None, range_length: None, text: "TODO".to_string() }];
+    notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    let DocumentDiagnosticReport::Full(report) = pull(&client, 4, &uri, Some(first_id.clone()))
+    else {
+        panic!("expected a full report after the change");
+    };
+    let full = report.full_document_diagnostic_report;
+    assert_eq!(full.items.len(), 1);
+    let second_id = full.result_id.unwrap();
+    assert_ne!(second_id, first_id);
+
+    // Only the document whose result id is out of date is checked again
+    let params = WorkspaceDiagnosticParams {
+        identifier: None,
+        previous_result_ids: vec![PreviousResultId { uri: uri.clone(), value: second_id.clone() }],
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    };
+    let WorkspaceDiagnosticReportResult::Report(report) = request::<WorkspaceDiagnosticRequest>(&client, 5, params)
+    else {
+        panic!("expected a workspace report");
+    };
+    let [WorkspaceDocumentDiagnosticReport::Unchanged(notes), WorkspaceDocumentDiagnosticReport::Full(other_report)] =
+        report.items.as_slice()
+    else {
+        panic!("expected one unchanged and one full report, got {:?}", report.items);
+    };
+    assert_eq!((&notes.uri, notes.version), (&uri, Some(2)));
+    assert_eq!(notes.unchanged_document_diagnostic_report.result_id, second_id);
+    assert_eq!((&other_report.uri, other_report.version), (&other, Some(1)));
+    assert!(other_report.full_document_diagnostic_report.items.is_empty());
+
+    // Changing another document does not make this one out of date
+    let text_document = VersionedTextDocumentIdentifier::new(other.clone(), 2);
+    let content_changes = vec![TextDocumentContentChangeEvent { range: None, range_length: None, text: "x".to_string() }];
+    notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    assert!(matches!(pull(&client, 6, &uri, Some(second_id)), DocumentDiagnosticReport::Unchanged(_)));
+
+    shutdown(&client, handle);
+}
+
+#[test]
+fn pull_with_inter_file_dependencies() {
+    let (server, client) = Connection::memory();
+    let server_thread = thread::spawn(move || {
+        LanguageServer::new(check).with_pull_diagnostics(true).with_inter_file_dependencies(true).run(&server)
+    });
+    let mut params = InitializeParams::default();
+    params.capabilities.text_document =
+        Some(TextDocumentClientCapabilities { diagnostic: Some(Default::default()), ..Default::default() });
+    let provider = initialize_with(&client, params).capabilities.diagnostic_provider;
+    assert!(matches!(provider, Some(DiagnosticServerCapabilities::Options(o)) if o.inter_file_dependencies));
+
+    let uri = Url::parse("untitled:notes").unwrap();
+    let other = Url::parse("untitled:other").unwrap();
+    for document in [&uri, &other] {
+        let text_document = TextDocumentItem::new(document.clone(), "text".to_string(), 1, "TODO".to_string());
+        notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    }
+    let DocumentDiagnosticReport::Full(report) = pull(&client, 2, &uri, None)
+    else {
+        panic!("expected a full report");
+    };
+    let result_id = report.full_document_diagnostic_report.result_id;
+    assert!(matches!(pull(&client, 3, &uri, result_id.clone()), DocumentDiagnosticReport::Unchanged(_)));
+
+    // Checks may read other documents, so changing one makes the others out of date as well
+    let text_document = VersionedTextDocumentIdentifier::new(other.clone(), 2);
+    let content_changes = vec![TextDocumentContentChangeEvent { range: None, range_length: None, text: "x".to_string() }];
+    notify::<DidChangeTextDocument>(&client, DidChangeTextDocumentParams { text_document, content_changes });
+    let DocumentDiagnosticReport::Full(report) = pull(&client, 4, &uri, result_id.clone())
+    else {
+        panic!("expected a full report after another document changed");
+    };
+    assert_ne!(report.full_document_diagnostic_report.result_id, result_id);
+
+    shutdown(&client, server_thread);
+}
+
+#[test]
+fn push_without_pull_support() {
+    let (server, client) = Connection::memory();
+    let handle = thread::spawn(move || LanguageServer::new(check).with_pull_diagnostics(true).run(&server));
+    assert!(initialize(&client).capabilities.diagnostic_provider.is_none());
+
+    let uri = Url::parse("untitled:notes").unwrap();
+    let text_document = TextDocumentItem::new(uri.clone(), "text".to_string(), 1, "TODO".to_string());
+    notify::<DidOpenTextDocument>(&client, DidOpenTextDocumentParams { text_document });
+    assert_eq!(published(&client).diagnostics.len(), 1);
+
+    shutdown(&client, handle);
+}
@@ -0,0 +1,114 @@
+use crate::{Diagnostic, Label, Suggestion};
+use serde::Serialize;
+use source_cache::{SourceCache, SourceSpan};
+use std::io::{Error, ErrorKind, Write};
+
+/// A [`Diagnostic`] as it is written by [`Diagnostic::write_json`].
+#[derive(Serialize)]
+struct JsonDiagnostic {
+    kind: String,
+    level: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<usize>,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<JsonLocation>,
+    labels: Vec<JsonLabel>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    suggestions: Vec<JsonSuggestion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    help: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+/// A span with its remapped path, and the one-based lines and columns it starts and ends at.
+///
+/// Columns count characters, `end_column` is the column after the last character.
+#[derive(Clone, Serialize)]
+pub(crate) struct JsonLocation {
+    pub path: String,
+    pub start: u32,
+    pub end: u32,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+#[derive(Serialize)]
+struct JsonLabel {
+    location: JsonLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonSuggestion {
+    location: JsonLocation,
+    replacement: String,
+    title: String,
+}
+
+impl JsonLocation {
+    /// Locate a span, gives `None` if its source is not in the cache or the span is outside of it.
+    pub fn new(cache: &SourceCache, span: SourceSpan) -> Option<Self> {
+        let src = cache.fetch(&span.file).ok()?;
+        let line_column = |offset: u32| {
+            let (line, index, column) = src.get_offset_line(offset)?;
+            let before = src.text().get(line.offset as usize..(line.offset + column) as usize)?;
+            Some((index + 1, before.chars().count() + 1))
+        };
+        let (line, column) = line_column(span.start)?;
+        let (end_line, end_column) = line_column(span.end.max(span.start))?;
+        let path = cache.display_path(&span.file).unwrap_or_else(|| "<unknown>".to_string());
+        Some(Self { path, start: span.start, end: span.end, line, column, end_line, end_column })
+    }
+}
+
+impl Diagnostic {
+    /// Write this diagnostic as a single line of JSON, for tools that read diagnostics rather than people.
+    ///
+    /// Spans are resolved like they are rendered, and paths are shown by [`SourceCache::display_path`], so they follow
+    /// the same [`PathRemap`](source_cache::PathRemap). Labels and suggestions whose source is not in the cache are left
+    /// out.
+    ///
+    /// ```json
+    /// {"kind":"ERROR","level":250,"code":3,"message":"Unknown variable","path":"src/main.x",
+    ///  "location":{"path":"src/main.x","start":8,"end":8,"line":1,"column":9,"end_line":1,"end_column":9},
+    ///  "labels":[{"location":{..},"message":"Not in scope"}]}
+    /// ```
+    pub fn write_json<W: Write>(&self, cache: &SourceCache, mut w: W) -> std::io::Result<()> {
+        let resolved = self.resolve_spans(cache);
+        let note = match (&self.note, resolved.notes.is_empty()) {
+            (note, true) => note.clone(),
+            (Some(note), false) => Some(format!("{}\n{}", note, resolved.notes.join("\n"))),
+            (None, false) => Some(resolved.notes.join("\n")),
+        };
+        let json = JsonDiagnostic {
+            kind: format!("{:?}", self.kind),
+            level: self.kind.level(),
+            code: self.code,
+            message: self.message.clone(),
+            path: cache.display_path(&resolved.file),
+            location: resolved.location.and_then(|at| JsonLocation::new(cache, resolved.file.with_range(at..at))),
+            labels: resolved.labels.iter().filter_map(|label| json_label(cache, label)).collect(),
+            suggestions: self.suggestions.iter().filter_map(|suggestion| json_suggestion(cache, suggestion)).collect(),
+            help: self.help.clone(),
+            note,
+        };
+        serde_json::to_writer(&mut w, &json).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        writeln!(w)
+    }
+}
+
+fn json_label(cache: &SourceCache, label: &Label) -> Option<JsonLabel> {
+    Some(JsonLabel { location: JsonLocation::new(cache, label.span)?, message: label.msg.clone() })
+}
+
+fn json_suggestion(cache: &SourceCache, suggestion: &Suggestion) -> Option<JsonSuggestion> {
+    let location = JsonLocation::new(cache, cache.resolve_embedded(suggestion.get_span()))?;
+    Some(JsonSuggestion { location, replacement: suggestion.get_replacement().to_string(), title: suggestion.get_title() })
+}
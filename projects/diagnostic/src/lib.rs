@@ -3,6 +3,11 @@
 
 mod display;
 mod draw;
+#[cfg(feature = "serde")]
+mod json;
+mod mapping;
+#[cfg(feature = "serde")]
+mod sarif;
 mod style;
 mod write;
 
@@ -10,6 +15,8 @@ mod characters;
 mod windows;
 
 use crate::{characters::Draw, display::*};
+#[cfg(feature = "serde")]
+pub use crate::sarif::write_sarif;
 pub use crate::{
     characters::{BuiltinDrawer, DrawElements},
     draw::{Console, Palette},
@@ -23,7 +30,6 @@ use core::{
 };
 pub use source_cache::{SourceCache, SourceID, SourceSpan};
 use std::io::Write;
-use unicode_width::UnicodeWidthChar;
 
 /// A type that represents a labelled section of identifier code.
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
@@ -81,6 +87,54 @@ impl Label {
         self.priority = priority;
         self
     }
+    /// Get the span this label points at.
+    pub fn get_span(&self) -> SourceSpan {
+        self.span
+    }
+    /// Get the message of this label.
+    pub fn get_message(&self) -> Option<&str> {
+        self.msg.as_deref()
+    }
+}
+
+/// A fix-it that replaces the text of a span, printed under the report and offered by editors as a quick fix.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Suggestion {
+    span: SourceSpan,
+    replacement: String,
+    msg: Option<String>,
+}
+
+impl Suggestion {
+    /// Create a new [`Suggestion`] that replaces the text of the span, an empty span inserts the replacement.
+    pub fn new<R: ToString>(span: SourceSpan, replacement: R) -> Self {
+        Self { span, replacement: replacement.to_string(), msg: None }
+    }
+    /// Describe what the suggestion does.
+    pub fn with_message<M: ToString>(mut self, msg: M) -> Self {
+        self.msg = Some(msg.to_string());
+        self
+    }
+    /// Get the span whose text is replaced.
+    pub fn get_span(&self) -> SourceSpan {
+        self.span
+    }
+    /// Get the text the span is replaced with.
+    pub fn get_replacement(&self) -> &str {
+        &self.replacement
+    }
+    /// Get the message of the suggestion.
+    pub fn get_message(&self) -> Option<&str> {
+        self.msg.as_deref()
+    }
+    /// Get the title of the suggestion, its message or a description of the edit if it has none.
+    pub fn get_title(&self) -> String {
+        match (&self.msg, self.replacement.is_empty()) {
+            (Some(msg), _) => msg.clone(),
+            (None, true) => "Remove this".to_string(),
+            (None, false) => format!("Replace with `{}`", self.replacement),
+        }
+    }
 }
 
 /// A type representing a diagnostic that is ready to be written to output.
@@ -93,6 +147,7 @@ pub struct Diagnostic {
     file: SourceID,
     location: Option<u32>,
     labels: Vec<Label>,
+    suggestions: Vec<Suggestion>,
     config: Config,
 }
 
@@ -117,6 +172,7 @@ impl Diagnostic {
                 file: Default::default(),
                 location: None,
                 labels: vec![],
+                suggestions: vec![],
                 config: Default::default(),
             },
         }
@@ -137,6 +193,41 @@ impl Diagnostic {
     }
 }
 
+impl Diagnostic {
+    /// Get the kind of this report.
+    pub fn get_kind(&self) -> &dyn ReportLevel {
+        self.kind.as_ref()
+    }
+    /// Get the numerical code of this report.
+    pub fn get_code(&self) -> Option<usize> {
+        self.code
+    }
+    /// Get the message of this report.
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+    /// Get the note of this report.
+    pub fn get_note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+    /// Get the help message of this report.
+    pub fn get_help(&self) -> Option<&str> {
+        self.help.as_deref()
+    }
+    /// Get the file this report is about, and the offset it starts at.
+    pub fn get_location(&self) -> (SourceID, Option<u32>) {
+        (self.file, self.location)
+    }
+    /// Get the labels of this report, in the order they were added.
+    pub fn get_labels(&self) -> &[Label] {
+        &self.labels
+    }
+    /// Get the suggestions of this report, in the order they were added.
+    pub fn get_suggestions(&self) -> &[Suggestion] {
+        &self.suggestions
+    }
+}
+
 impl Debug for Diagnostic {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Report")
@@ -297,6 +388,17 @@ impl DiagnosticBuilder {
         self
     }
 
+    /// Add a suggestion to the report.
+    pub fn add_suggestion(&mut self, suggestion: Suggestion) {
+        self.inner.suggestions.push(suggestion);
+    }
+
+    /// Add a suggestion to the report.
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.add_suggestion(suggestion);
+        self
+    }
+
     /// Use the given [`Config`] to determine diagnostic attributes.
     pub fn with_config(mut self, config: Config) -> Self {
         self.inner.config = config;
@@ -343,6 +445,10 @@ pub struct Config {
     /// custom important
     pub unimportant_color: Option<Color>,
     tab_width: usize,
+    source_maps: bool,
+    file_offsets: bool,
+    line_endings: bool,
+    visual_columns: bool,
     /// Custom character sets
     pub characters: DrawElements,
 }
@@ -399,6 +505,38 @@ impl Config {
         self.tab_width = tab_width;
         self
     }
+    /// Should spans in generated code be shown in the original code, following the source maps registered with
+    /// [`SourceCache::add_source_map`]?
+    ///
+    /// Each mapped span adds a note with its location in the generated code. If unspecified, this defaults to [`false`].
+    pub fn with_source_maps(mut self, source_maps: bool) -> Self {
+        self.source_maps = source_maps;
+        self
+    }
+    /// Are label spans byte offsets into the files as stored, rather than into the decoded text?
+    ///
+    /// Set this when spans come from a tool working on the raw bytes of files that are not plain UTF-8, see
+    /// [`SourceText::get_text_span`](source_cache::SourceText::get_text_span). If unspecified, this defaults to [`false`].
+    pub fn with_file_offsets(mut self, file_offsets: bool) -> Self {
+        self.file_offsets = file_offsets;
+        self
+    }
+    /// Should line terminators covered by a label be shown as a visible marker, such as `␍␊` for CRLF?
+    ///
+    /// Useful for diagnostics about line endings themselves. If unspecified, this defaults to [`false`].
+    pub fn with_line_endings(mut self, line_endings: bool) -> Self {
+        self.line_endings = line_endings;
+        self
+    }
+    /// Should the column in `line:col` headers count visual columns, expanding tabs to the tab width and counting wide
+    /// characters twice, instead of bytes?
+    ///
+    /// See [`SourceLine::get_visual_column`](source_cache::SourceLine::get_visual_column). If unspecified, this defaults
+    /// to [`false`].
+    pub fn with_visual_columns(mut self, visual_columns: bool) -> Self {
+        self.visual_columns = visual_columns;
+        self
+    }
     /// What character set should be used to display dynamic elements such as boxes and arrows?
     ///
     /// If unspecified, this defaults to [`BuiltinDrawer::Unicode`].
@@ -437,15 +575,8 @@ impl Config {
 
     // Find the character that should be drawn and the number of times it should be drawn for each char
     fn char_width(&self, c: char, col: usize) -> (char, usize) {
-        match c {
-            '\t' => {
-                // Find the column that the tab should end at
-                let tab_end = (col / self.tab_width + 1) * self.tab_width;
-                (' ', tab_end - col)
-            }
-            c if c.is_whitespace() => (' ', 1),
-            _ => (c, c.width().unwrap_or(1)),
-        }
+        let width = source_cache::visual_width(c, col, self.tab_width);
+        if c.is_whitespace() { (' ', width) } else { (c, width) }
     }
 }
 
@@ -462,6 +593,10 @@ impl Default for Config {
             margin_skip_color: None,
             unimportant_color: None,
             tab_width: 4,
+            source_maps: false,
+            file_offsets: false,
+            line_endings: false,
+            visual_columns: false,
             characters: BuiltinDrawer::Unicode.get_elements(),
         }
     }
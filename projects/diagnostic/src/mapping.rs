@@ -0,0 +1,72 @@
+use crate::{Diagnostic, Label};
+use source_cache::{SourceCache, SourceID, SourceSpan};
+use std::borrow::Cow;
+
+/// The labels and location of a [`Diagnostic`] as they are rendered, after following source maps.
+pub(crate) struct ResolvedSpans<'a> {
+    pub labels: Cow<'a, [Label]>,
+    pub file: SourceID,
+    pub location: Option<u32>,
+    /// Where the mapped spans were in the generated code
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    /// Move spans from file offsets to text offsets if [`Config::with_file_offsets`](crate::Config) is set, out of
+    /// embedded sources into the sources they are embedded in, then from generated code to the original code if
+    /// [`Config::with_source_maps`](crate::Config) is set.
+    pub(crate) fn resolve_spans(&self, cache: &SourceCache) -> ResolvedSpans<'_> {
+        let mut resolved =
+            ResolvedSpans { labels: Cow::Borrowed(&self.labels), file: self.file, location: self.location, notes: vec![] };
+        if self.config.file_offsets {
+            let to_text = |span: SourceSpan| cache.fetch(&span.file).map_or(span, |src| src.get_text_span(span));
+            resolved.location = self.location.map(|location| to_text(self.file.with_range(location..location)).start);
+            resolved.labels.to_mut().iter_mut().for_each(|label| label.span = to_text(label.span));
+        }
+        let location = resolved.location.unwrap_or_default();
+        let outer = cache.resolve_embedded(resolved.file.with_range(location..location));
+        resolved.file = outer.file;
+        resolved.location = resolved.location.map(|_| outer.start);
+        if resolved.labels.iter().any(|label| cache.resolve_embedded(label.span) != label.span) {
+            resolved.labels.to_mut().iter_mut().for_each(|label| label.span = cache.resolve_embedded(label.span));
+        }
+        if !self.config.source_maps {
+            return resolved;
+        }
+        let mut generated = vec![];
+        if let Some(location) = resolved.location {
+            let span = resolved.file.with_range(location..location);
+            if let Some(mapped) = cache.map_span(span) {
+                generated.push(span);
+                resolved.file = mapped.file;
+                resolved.location = Some(mapped.start);
+            }
+        }
+        let mut labels = resolved.labels.to_vec();
+        for label in labels.iter_mut() {
+            if let Some(mapped) = cache.map_span(label.span) {
+                generated.push(label.span);
+                label.span = mapped;
+            }
+        }
+        if generated.is_empty() {
+            return resolved;
+        }
+        resolved.labels = Cow::Owned(labels);
+        for span in generated {
+            let note = generated_at(cache, span);
+            if !resolved.notes.contains(&note) {
+                resolved.notes.push(note);
+            }
+        }
+        resolved
+    }
+}
+
+fn generated_at(cache: &SourceCache, span: SourceSpan) -> String {
+    let path = cache.display_path(&span.file).unwrap_or_else(|| "<unknown>".to_string());
+    match cache.fetch(&span.file).ok().and_then(|src| src.get_offset_line(span.start)) {
+        Some((_, line, column)) => format!("generated at {}:{}:{}", path, line + 1, column + 1),
+        None => format!("generated at {}", path),
+    }
+}
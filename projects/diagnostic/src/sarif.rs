@@ -0,0 +1,191 @@
+use crate::{json::JsonLocation, Diagnostic};
+use serde::Serialize;
+use source_cache::SourceCache;
+use std::io::{Error, ErrorKind, Write};
+
+#[derive(Serialize)]
+struct SarifLog {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<SarifRun>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRun {
+    tool: SarifTool,
+    column_kind: &'static str,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct SarifTool {
+    driver: SarifDriver,
+}
+
+#[derive(Serialize)]
+struct SarifDriver {
+    name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rule_id: Option<String>,
+    level: &'static str,
+    message: SarifMessage,
+    locations: Vec<SarifLocation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    related_locations: Vec<SarifLocation>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fixes: Vec<SarifFix>,
+}
+
+#[derive(Serialize)]
+struct SarifMessage {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifLocation {
+    physical_location: SarifPhysicalLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<SarifMessage>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifPhysicalLocation {
+    artifact_location: SarifArtifactLocation,
+    region: SarifRegion,
+}
+
+#[derive(Serialize)]
+struct SarifArtifactLocation {
+    uri: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifRegion {
+    start_line: usize,
+    start_column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifFix {
+    description: SarifMessage,
+    artifact_changes: Vec<SarifArtifactChange>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifArtifactChange {
+    artifact_location: SarifArtifactLocation,
+    replacements: Vec<SarifReplacement>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifReplacement {
+    deleted_region: SarifRegion,
+    inserted_content: SarifMessage,
+}
+
+impl SarifPhysicalLocation {
+    fn new(location: JsonLocation) -> Self {
+        Self {
+            artifact_location: SarifArtifactLocation { uri: location.path },
+            region: SarifRegion {
+                start_line: location.line,
+                start_column: location.column,
+                end_line: location.end_line,
+                end_column: location.end_column,
+            },
+        }
+    }
+}
+
+/// Write the diagnostics of one run of a tool as a [SARIF 2.1.0](https://docs.oasis-open.org/sarif/sarif/v2.1.0/) log,
+/// the format code scanning services take.
+///
+/// Each diagnostic is placed on its first label, or at its location if it has no labels, the other labels become
+/// related locations and its suggestions fixes. Paths are shown by [`SourceCache::display_path`], like in rendered
+/// reports and [`Diagnostic::write_json`].
+pub fn write_sarif<'a, I, W>(cache: &SourceCache, tool: &str, diagnostics: I, mut w: W) -> std::io::Result<()>
+where
+    I: IntoIterator<Item = &'a Diagnostic>,
+    W: Write,
+{
+    let results = diagnostics.into_iter().map(|diagnostic| sarif_result(cache, diagnostic)).collect();
+    let log = SarifLog {
+        schema: "https://json.schemastore.org/sarif-2.1.0.json",
+        version: "2.1.0",
+        runs: vec![SarifRun {
+            tool: SarifTool { driver: SarifDriver { name: tool.to_string() } },
+            column_kind: "unicodeCodePoints",
+            results,
+        }],
+    };
+    serde_json::to_writer(&mut w, &log).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    writeln!(w)
+}
+
+fn sarif_result(cache: &SourceCache, diagnostic: &Diagnostic) -> SarifResult {
+    let resolved = diagnostic.resolve_spans(cache);
+    let mut labels = resolved.labels.iter().filter_map(|label| Some((JsonLocation::new(cache, label.span)?, label)));
+    let at = resolved.location.and_then(|at| JsonLocation::new(cache, resolved.file.with_range(at..at)));
+    let locations = match labels.next() {
+        Some((location, _)) => vec![location],
+        None => at.into_iter().collect(),
+    };
+    let related_locations = labels
+        .map(|(location, label)| SarifLocation {
+            physical_location: SarifPhysicalLocation::new(location),
+            message: label.msg.clone().map(|text| SarifMessage { text }),
+        })
+        .collect();
+    let fixes = diagnostic
+        .suggestions
+        .iter()
+        .filter_map(|suggestion| {
+            let location = JsonLocation::new(cache, cache.resolve_embedded(suggestion.get_span()))?;
+            let SarifPhysicalLocation { artifact_location, region } = SarifPhysicalLocation::new(location);
+            let replacement = SarifReplacement {
+                deleted_region: region,
+                inserted_content: SarifMessage { text: suggestion.get_replacement().to_string() },
+            };
+            Some(SarifFix {
+                description: SarifMessage { text: suggestion.get_title() },
+                artifact_changes: vec![SarifArtifactChange { artifact_location, replacements: vec![replacement] }],
+            })
+        })
+        .collect();
+    let mut message = diagnostic.message.clone();
+    for extra in diagnostic.help.iter().chain(&diagnostic.note).chain(&resolved.notes) {
+        message.push('\n');
+        message.push_str(extra);
+    }
+    SarifResult {
+        rule_id: diagnostic.code.map(|code| format!("{:04}", code)),
+        level: match diagnostic.kind.level() {
+            250.. => "error",
+            200.. => "warning",
+            150.. => "note",
+            _ => "none",
+        },
+        message: SarifMessage { text: message },
+        locations: locations
+            .into_iter()
+            .map(|location| SarifLocation { physical_location: SarifPhysicalLocation::new(location), message: None })
+            .collect(),
+        related_locations,
+        fixes,
+    }
+}
@@ -1,5 +1,5 @@
-use crate::SourceID;
-use source_cache::{SourceCache, SourceText};
+use crate::{mapping::ResolvedSpans, SourceID};
+use source_cache::{LineEnding, SourceCache, SourcePath, SourceText};
 use std::ops::Range;
 
 use super::{
@@ -32,13 +32,13 @@ struct SourceGroup<'a> {
 }
 
 impl Diagnostic {
-    fn get_source_groups(&self, cache: &SourceCache) -> Vec<SourceGroup> {
+    fn get_source_groups<'a>(&self, labels: &'a [Label], cache: &SourceCache) -> Vec<SourceGroup<'a>> {
         let mut groups = Vec::new();
-        for label in self.labels.iter() {
+        for label in labels.iter() {
             let src = match cache.fetch(&label.span.file) {
                 Ok(src) => src,
                 Err(e) => {
-                    let src_display = cache.source_path(&label.span.file);
+                    let src_display = cache.display_path(&label.span.file);
                     eprintln!("Unable to fetch identifier '{}': {:?}", Show(src_display), e);
                     continue;
                 }
@@ -102,13 +102,14 @@ impl Diagnostic {
         else {
             writeln!(w, " {}", self.message)?;
         }
-        let groups = self.get_source_groups(&cache);
+        let resolved = self.resolve_spans(cache);
+        let groups = self.get_source_groups(&resolved.labels, cache);
 
         // Line number maximum width
         let line_no_width = groups
             .iter()
             .filter_map(|SourceGroup { span, id: src_id, .. }| {
-                let src_name = cache.source_path(src_id).map(|d| d.to_string()).unwrap_or_else(|| "<unknown>".to_string());
+                let src_name = cache.display_path(src_id).unwrap_or_else(|| "<unknown>".to_string());
 
                 let src = match cache.fetch(src_id) {
                     Ok(src) => src,
@@ -127,7 +128,7 @@ impl Diagnostic {
         // --- Source sections ---
         let groups_len = groups.len();
         for (group_idx, SourceGroup { id: src_id, span, labels }) in groups.into_iter().enumerate() {
-            let src_name = cache.source_path(src_id).map(|d| d.to_string()).unwrap_or_else(|| "<unknown>".to_string());
+            let src_name = cache.display_path(src_id).unwrap_or_else(|| "<unknown>".to_string());
 
             let src = match cache.fetch(src_id) {
                 Ok(src) => src,
@@ -138,7 +139,7 @@ impl Diagnostic {
             };
 
             let line_range = src.get_line_range(&span);
-            let line_ref = self.get_line_column(src_id, &labels, src);
+            let line_ref = self.get_line_column(&resolved, src_id, &labels, src);
             // File name & reference
             writeln!(
                 w,
@@ -465,7 +466,7 @@ impl Diagnostic {
 
                 // Line
                 if !is_ellipsis {
-                    for (col, c) in line.chars().enumerate() {
+                    for (col, c) in line.chars(src).enumerate() {
                         let color = if let Some(highlight) = get_highlight(col as u32) {
                             highlight.color
                         }
@@ -482,8 +483,17 @@ impl Diagnostic {
                             write!(w, "{}", c.fg(color, s))?;
                         };
                     }
+                    let ending = line.ending_range();
+                    let ending_label = get_highlight(ending.start - line.offset);
+                    if let Some(label) = ending_label.filter(|_| self.config.line_endings && !ending.is_empty()) {
+                        // Trailing whitespace keeps the marker under the column its arrows point at
+                        let gap = src.text()[line.text.end as usize..ending.start as usize].chars().count();
+                        write!(w, "{}", Show((' ', gap)))?;
+                        let marker = format!("{:<1$}", ending_marker(line.ending), ending.len());
+                        write!(w, "{}", marker.fg(label.color, s))?;
+                    }
                 }
-                write!(w, "\n")?;
+                writeln!(w)?;
 
                 // Arrows
                 for row in 0..line_labels.len() {
@@ -493,7 +503,7 @@ impl Diagnostic {
                         // Margin alternate
                         write_margin(&mut w, idx, false, is_ellipsis, true, Some((row, false)), &line_labels, &margin_label)?;
                         // Lines alternate
-                        let mut chars = line.chars();
+                        let mut chars = line.chars(src);
                         for col in 0..arrow_len {
                             let width = chars.next().map_or(1, |c| self.config.char_width(c, col as usize).1);
 
@@ -540,7 +550,7 @@ impl Diagnostic {
                     // Margin
                     write_margin(&mut w, idx, false, is_ellipsis, true, Some((row, true)), &line_labels, &margin_label)?;
                     // Lines
-                    let mut chars = line.chars();
+                    let mut chars = line.chars(src);
                     for col in 0..arrow_len {
                         let width = chars.next().map_or(1, |c| self.config.char_width(c, col as usize).1);
 
@@ -612,6 +622,16 @@ impl Diagnostic {
                 write!(w, "{}: {}\n", "Help".fg(self.config.note_color(), s), note)?;
             }
 
+            // Suggestions
+            for suggestion in self.suggestions.iter().filter(|_| is_final_group) {
+                if !self.config.compact {
+                    write_margin(&mut w, 0, false, false, true, Some((0, false)), &[], &None)?;
+                    writeln!(w)?;
+                }
+                write_margin(&mut w, 0, false, false, true, Some((0, false)), &[], &None)?;
+                writeln!(w, "{}: {}", "Fix".fg(self.config.note_color(), s), suggestion.get_title())?;
+            }
+
             // Note
             if let (Some(note), true) = (&self.note, is_final_group) {
                 if !self.config.compact {
@@ -622,6 +642,16 @@ impl Diagnostic {
                 write!(w, "{}: {}\n", "Note".fg(self.config.note_color(), s), note)?;
             }
 
+            // Locations in generated code
+            for note in resolved.notes.iter().filter(|_| is_final_group) {
+                if !self.config.compact {
+                    write_margin(&mut w, 0, false, false, true, Some((0, false)), &[], &None)?;
+                    writeln!(w)?;
+                }
+                write_margin(&mut w, 0, false, false, true, Some((0, false)), &[], &None)?;
+                writeln!(w, "{}: {}", "Note".fg(self.config.note_color(), s), note)?;
+            }
+
             // Tail of report
             if !self.config.compact {
                 if is_final_group {
@@ -636,9 +666,9 @@ impl Diagnostic {
         Ok(())
     }
 
-    fn get_line_column(&self, src_id: &SourceID, labels: &[LabelInfo], src: &SourceText) -> String {
-        let location = if src_id == &self.file {
-            match self.location {
+    fn get_line_column(&self, resolved: &ResolvedSpans, src_id: &SourceID, labels: &[LabelInfo], src: &SourceText) -> String {
+        let location = if src_id == &resolved.file {
+            match resolved.location {
                 Some(s) => s,
                 None => return String::new(),
             }
@@ -650,13 +680,36 @@ impl Diagnostic {
             None => {
                 format!(":{}!", location)
             }
-            Some((_, idx, col)) => {
-                format!(":{}:{}", idx + 1, col + 1)
+            Some((line, idx, col)) => {
+                let col = match self.config.visual_columns {
+                    true => line.get_visual_column(src, col, self.config.tab_width) as u32,
+                    false => col,
+                };
+                match src.get_source() {
+                    // Cells are numbered in the header already, `:line:col` would read as part of the path
+                    SourcePath::NotebookCell { .. } => format!(", line {}, column {}", idx + 1, col + 1),
+                    _ => format!(":{}:{}", idx + 1, col + 1),
+                }
             }
         }
     }
 }
 
+/// The visible marker of a line terminator, see [`Config::with_line_endings`](crate::Config::with_line_endings).
+fn ending_marker(ending: LineEnding) -> &'static str {
+    match ending {
+        LineEnding::None => "",
+        LineEnding::Lf => "␊",
+        LineEnding::CrLf => "␍␊",
+        LineEnding::Cr => "␍",
+        LineEnding::VerticalTab => "␋",
+        LineEnding::FormFeed => "␌",
+        LineEnding::NextLine => "␤",
+        LineEnding::LineSeparator => "⏎",
+        LineEnding::ParagraphSeparator => "¶",
+    }
+}
+
 impl Label {
     fn last_offset(&self) -> u32 {
         self.span.end.saturating_sub(1).max(self.span.start)
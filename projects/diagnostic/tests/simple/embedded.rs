@@ -0,0 +1,24 @@
+use super::*;
+
+#[test]
+fn nested_regions() {
+    let mut store = SourceCache::default();
+    let doc = store.load_text("# Queries\n\n```rust\nlet q = sql!(\"SELECT nme FROM users\");\n```\n", "guide.md").unwrap();
+    let rust = store.add_embedded(doc.with_range(19..58)).unwrap();
+    assert_eq!(store.fetch(&rust).unwrap().text(), "let q = sql!(\"SELECT nme FROM users\");\n");
+    let sql = store.add_embedded(rust.with_range(14..35)).unwrap();
+    assert_eq!(store.fetch(&sql).unwrap().text(), "SELECT nme FROM users");
+
+    let mut out = Vec::new();
+    Diagnostic::new(ReportKind::Error)
+        .with_location(sql, Some(7))
+        .with_message("Unknown column `nme`")
+        .with_label(Label::new(sql.with_range(7..10)).with_message("Did you mean `name`?"))
+        .with_config(Config::default().with_color(false))
+        .finish()
+        .write(&store, &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("guide.md:4:22"), "{}", out);
+    assert!(out.contains(" 4 │ let q = sql!(\"SELECT nme FROM users\");"), "{}", out);
+}
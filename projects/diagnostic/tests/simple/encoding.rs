@@ -0,0 +1,72 @@
+use super::*;
+use source_cache::TextDecoder;
+
+#[test]
+fn file_offsets() {
+    let mut store = SourceCache::default();
+    let mut source = TextDecoder::default().with_lossy(true).decode(b"x\xFFy = bad\n".to_vec()).unwrap();
+    source.set_source(source_cache::SourcePath::Snippet("legacy.cfg".into()));
+    let file = store.insert(source).unwrap();
+
+    let diagnostic = |config: Config| {
+        let mut out = Vec::new();
+        Diagnostic::new(ReportKind::Error)
+            .with_location(file, Some(6))
+            .with_message("Unknown value")
+            .with_label(Label::new(file.with_range(6..9)).with_message("Expected a number"))
+            .with_config(config.with_color(false))
+            .finish()
+            .write(&store, &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert!(diagnostic(Config::default()).contains("legacy.cfg:1:7"));
+    let mapped = diagnostic(Config::default().with_file_offsets(true));
+    assert!(mapped.contains("legacy.cfg:1:9"));
+    assert!(mapped.contains("  ─┬─"));
+}
+
+#[test]
+fn line_ending_markers() {
+    let mut store = SourceCache::default();
+    let file = store.load_text("let x = 1;\r\nlet y = 2;\nlet z = 3;\n", "mixed.x").unwrap();
+    let ending = store.fetch(&file).unwrap().get_mixed_endings()[0];
+
+    let diagnostic = |config: Config| {
+        let mut out = Vec::new();
+        Diagnostic::new(ReportKind::Alert)
+            .with_location(file, Some(ending.start))
+            .with_message("Mixed line endings")
+            .with_label(Label::new(ending).with_message("CRLF, other lines use LF"))
+            .with_config(config.with_color(false))
+            .finish()
+            .write(&store, &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert!(!diagnostic(Config::default()).contains("␍␊"));
+    let marked = diagnostic(Config::default().with_line_endings(true));
+    assert!(marked.contains("let x = 1;␍␊"), "{}", marked);
+    assert!(marked.contains("│           ─┬"), "{}", marked);
+}
+
+#[test]
+fn visual_columns() {
+    let mut store = SourceCache::default();
+    let file = store.load_text("\tlet 名前 = 1;\n", "wide.x").unwrap();
+
+    let diagnostic = |config: Config| {
+        let mut out = Vec::new();
+        Diagnostic::new(ReportKind::Error)
+            .with_location(file, Some(12))
+            .with_message("Expected a string")
+            .with_label(Label::new(file.with_range(12..13)))
+            .with_config(config.with_color(false))
+            .finish()
+            .write(&store, &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    assert!(diagnostic(Config::default()).contains("wide.x:1:13"));
+    assert!(diagnostic(Config::default().with_visual_columns(true)).contains("wide.x:1:14"));
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "serde")]
+use super::*;
+use diagnostic::write_sarif;
+use serde_json::Value;
+use source_cache::PathRemap;
+
+fn report(store: &mut SourceCache) -> Diagnostic {
+    store.set_path_remap(PathRemap::default().with_root("/work/app"));
+    let file = store.open_buffer("/work/app/src/main.x", "let é = 1;\nprint(nmae);").unwrap();
+    Diagnostic::new(ReportKind::Error)
+        .with_code(3)
+        .with_location(file, Some(18))
+        .with_message("Unknown variable")
+        .with_label(Label::new(file.with_range(18..22)).with_message("Not in scope"))
+        .with_label(Label::new(file.with_range(4..6)).with_message("Similar name"))
+        .with_suggestion(Suggestion::new(file.with_range(18..22), "name"))
+        .finish()
+}
+
+#[test]
+fn json_lines() {
+    let mut store = SourceCache::default();
+    let mut out = Vec::new();
+    report(&mut store).write_json(&store, &mut out).unwrap();
+    let json: Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(json["kind"], "ERROR");
+    assert_eq!(json["code"], 3);
+    assert_eq!(json["path"], "src/main.x");
+    let label = &json["labels"][1]["location"];
+    assert_eq!(
+        (&label["path"], &label["line"], &label["column"], &label["end_column"]),
+        (&"src/main.x".into(), &1.into(), &5.into(), &6.into())
+    );
+    assert_eq!(json["suggestions"][0]["title"], "Replace with `name`");
+}
+
+#[test]
+fn sarif_log() {
+    let mut store = SourceCache::default();
+    let report = report(&mut store);
+    let mut out = Vec::new();
+    write_sarif(&store, "checker", [&report], &mut out).unwrap();
+    let sarif: Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["ruleId"], "0003");
+    assert_eq!(result["level"], "error");
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "src/main.x");
+    assert_eq!(location["region"]["startLine"], 2);
+    assert_eq!(location["region"]["startColumn"], 7);
+    assert_eq!(result["relatedLocations"][0]["message"]["text"], "Similar name");
+    assert_eq!(result["fixes"][0]["artifactChanges"][0]["replacements"][0]["insertedContent"]["text"], "name");
+}
@@ -1,22 +1,28 @@
-use diagnostic::{enable_ansi_color, Color, Config, Console, Diagnostic, Label, Palette, ReportKind, SourceID};
+use diagnostic::{enable_ansi_color, Color, Config, Console, Diagnostic, Label, Palette, ReportKind, SourceID, Suggestion};
 use source_cache::{SourceCache, SourceText};
 use std::{iter::zip, ops::Range};
 
+mod embedded;
+mod encoding;
+mod json;
 mod multi_file;
 mod multi_line;
+mod notebook;
+mod source_map;
 mod stress_test;
+mod suggestion;
 
 fn debug_lines(lines: Vec<&str>) {
     let source: String = lines.iter().map(|s| *s).collect();
     let source = SourceText::from(source);
 
-    assert_eq!(source.lines().len(), lines.len());
+    assert_eq!(source.get_line_count(), lines.len());
 
     let mut offset = 0;
-    for (source_line, raw_line) in zip(source.lines().into_iter(), lines.into_iter()) {
+    for (source_line, raw_line) in zip(source.lines(), lines) {
         assert_eq!(source_line.offset as usize, offset);
         assert_eq!(source_line.length as usize, raw_line.len());
-        assert_eq!(source_line.text, raw_line.trim_end());
+        assert_eq!(source_line.view(&source), raw_line.trim_end());
         offset += source_line.length as usize;
     }
 
@@ -26,7 +32,7 @@ fn debug_lines(lines: Vec<&str>) {
 #[test]
 fn simple() {
     let mut files = SourceCache::default();
-    let sample = files.load_text(include_str!("sample.tao"), "sample.tao");
+    let sample = files.load_text(include_str!("sample.tao"), "sample.tao").unwrap();
 
     Diagnostic::new(ReportKind::Blame)
         .with_location(sample, Some(12))
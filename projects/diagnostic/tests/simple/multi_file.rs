@@ -11,8 +11,8 @@ fn main() {
     let c = colors.random();
 
     let mut store = SourceCache::default();
-    let file_a = store.load_text(include_str!("a.tao"), "a.tao");
-    let file_b = store.load_text(include_str!("b.tao"), "b.tao");
+    let file_a = store.load_text(include_str!("a.tao"), "a.tao").unwrap();
+    let file_b = store.load_text(include_str!("b.tao"), "b.tao").unwrap();
 
     Diagnostic::new(ReportKind::Error)
         .with_location(file_b, Some(10))
@@ -40,3 +40,22 @@ fn main() {
         .print(&store)
         .unwrap();
 }
+
+#[test]
+fn remapped_paths() {
+    let mut store = SourceCache::default();
+    let file = store.open_buffer("/runner/work/app/src/main.tao", "def five = 5").unwrap();
+    store.set_path_remap(source_cache::PathRemap::default().with_root("/runner/work/app"));
+    let mut out = Vec::new();
+    Diagnostic::new(ReportKind::Error)
+        .with_location(file, Some(4))
+        .with_message("Unused definition")
+        .with_label(Label::new(file.with_range(4..8)).with_message("Never used"))
+        .with_config(Config::default().with_color(false))
+        .finish()
+        .write(&store, &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("─[src/main.tao:1:5]"), "{}", out);
+    assert!(!out.contains("runner"));
+}
@@ -11,7 +11,7 @@ fn main() {
     let out2 = colors.random();
 
     let mut files = SourceCache::default();
-    let sample = files.load_text(include_str!("sample.tao"), "sample.tao");
+    let sample = files.load_text(include_str!("sample.tao"), "sample.tao").unwrap();
 
     Diagnostic::new(ReportKind::Error)
         .with_location(sample, Some(12))
@@ -0,0 +1,29 @@
+#![cfg(feature = "notebook")]
+use super::*;
+
+#[test]
+fn cell_headers() {
+    let notebook = r##"{"cells": [
+        {"cell_type": "markdown", "source": ["# Load"]},
+        {"cell_type": "code", "source": ["df = load(\"data.csv\")\n", "df.plot(kind=\"bars\")"]}
+    ], "metadata": {}, "nbformat": 4, "nbformat_minor": 5}"##;
+    let path = std::env::temp_dir().join(format!("diagnostic-{}-report.ipynb", std::process::id()));
+    std::fs::write(&path, notebook).unwrap();
+    let mut store = SourceCache::default();
+    store.set_path_remap(source_cache::PathRemap::default().with_root(std::env::temp_dir()));
+    let cell = store.load_notebook(&path).unwrap()[0];
+
+    let mut out = Vec::new();
+    Diagnostic::new(ReportKind::Error)
+        .with_location(cell, Some(36))
+        .with_message("Unknown plot kind")
+        .with_label(Label::new(cell.with_range(36..42)).with_message("Expected `bar`"))
+        .with_config(Config::default().with_color(false))
+        .finish()
+        .write(&store, &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("report.ipynb, cell [2], line 2, column 15]"), "{}", out);
+    assert!(out.contains(" 2 │ df.plot(kind=\"bars\")"), "{}", out);
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,34 @@
+use super::*;
+use source_cache::SourceMap;
+
+#[test]
+fn generated_code() {
+    let mut store = SourceCache::default();
+    let template = store.load_text("<p>{{ user.nmae }}</p>", "profile.html").unwrap();
+    let output = store.load_text("out.push('<p>');\nout.push(user.nmae);\nout.push('</p>');\n", "profile.js").unwrap();
+    let mut map = SourceMap::new(output);
+    map.add_unmapped(0);
+    map.add_mapping(26, template, 6);
+    map.add_unmapped(36);
+    store.add_source_map(map);
+
+    let diagnostic = |config: Config| {
+        let mut out = Vec::new();
+        Diagnostic::new(ReportKind::Error)
+            .with_location(output, Some(31))
+            .with_message("Unknown field `nmae`")
+            .with_label(Label::new(output.with_range(31..35)).with_message("Did you mean `name`?"))
+            .with_config(config.with_color(false))
+            .finish()
+            .write(&store, &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    };
+    let generated = diagnostic(Config::default());
+    assert!(generated.contains("profile.js:2:15"));
+    assert!(!generated.contains("profile.html"));
+    let original = diagnostic(Config::default().with_source_maps(true));
+    assert!(original.contains("profile.html:1:12"));
+    assert!(original.contains("<p>{{ user.nmae }}</p>"));
+    assert!(original.contains("Note: generated at profile.js:2:15"));
+}
@@ -3,7 +3,7 @@ use super::*;
 #[test]
 fn main() {
     let mut files = SourceCache::default();
-    let stress = files.load_text(include_str!("stresstest.tao"), "stresstest.tao");
+    let stress = files.load_text(include_str!("stresstest.tao"), "stresstest.tao").unwrap();
 
     let mut colors = Palette::new();
 
@@ -0,0 +1,22 @@
+use super::*;
+
+#[test]
+fn fix_it() {
+    let mut store = SourceCache::default();
+    let file = store.load_text("let nmae = 1;\nprint(nmae);", "main.x").unwrap();
+    let mut out = Vec::new();
+    Diagnostic::new(ReportKind::Error)
+        .with_location(file, Some(20))
+        .with_message("Unknown variable `nmae`")
+        .with_label(Label::new(file.with_range(20..24)).with_message("Not defined"))
+        .with_suggestion(Suggestion::new(file.with_range(20..24), "name").with_message("Use `name`"))
+        .with_suggestion(Suggestion::new(file.with_range(12..13), ""))
+        .with_config(Config::default().with_color(false))
+        .finish()
+        .write(&store, &mut out)
+        .unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("Fix: Use `name`\n"), "{}", out);
+    assert!(out.contains("Fix: Remove this\n"), "{}", out);
+    assert_eq!(Suggestion::new(file.with_range(0..3), "const").get_title(), "Replace with `const`");
+}
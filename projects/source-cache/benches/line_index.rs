@@ -0,0 +1,48 @@
+use criterion::{BenchmarkId, Criterion, Throughput, black_box, criterion_group, criterion_main};
+use source_cache::SourceText;
+
+/// Build a source file of roughly `lines` lines with a mix of line lengths and line endings.
+fn generate(lines: usize) -> String {
+    let mut text = String::with_capacity(lines * 40);
+    for i in 0..lines {
+        match i % 7 {
+            0 => text.push_str("fn main() {\n"),
+            1 => text.push_str("    let value = compute(42, \"text\");    \n"),
+            2 => text.push_str("\tif value > 0 { return value; }\r\n"),
+            3 => text.push('\n'),
+            4 => text.push_str("    // ünïcödé cömmënt with 𐐀 in it\n"),
+            5 => text.push_str("    println!(\"{}\", value);\n"),
+            _ => text.push_str("}\n"),
+        }
+    }
+    text
+}
+
+/// The line table as it was built before lines borrowed from the source, one allocation per line.
+fn owned_lines(text: &str) -> Vec<(u32, u32, String)> {
+    let mut offset = 0;
+    text.split_inclusive(['\r', '\n', '\x0B', '\x0C', '\u{0085}', '\u{2028}', '\u{2029}'])
+        .map(|line| {
+            let item = (offset, line.len() as u32, line.trim_end().to_owned());
+            offset += line.len() as u32;
+            item
+        })
+        .collect()
+}
+
+fn line_index(c: &mut Criterion) {
+    let mut group = c.benchmark_group("line_index");
+    for lines in [1_000, 100_000] {
+        let text = generate(lines);
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group
+            .bench_with_input(BenchmarkId::new("owned_lines", lines), &text, |b, text| b.iter(|| owned_lines(black_box(text))));
+        group.bench_with_input(BenchmarkId::new("source_text", lines), &text, |b, text| {
+            b.iter(|| SourceText::from(black_box(text.as_str())))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, line_index);
+criterion_main!(benches);
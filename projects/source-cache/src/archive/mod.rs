@@ -0,0 +1,188 @@
+use crate::{SourceCache, SourceID, SourcePath, TextDecoder};
+#[cfg(any(feature = "zip", feature = "tar"))]
+use std::fs::File;
+#[cfg(any(feature = "zip", feature = "tar"))]
+use std::io::Read;
+use std::{
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+/// The most bytes read from a single file in an archive, the sizes archives claim for their files are not trusted.
+#[cfg(any(feature = "zip", feature = "tar"))]
+const MAX_ENTRY_SIZE: u64 = 64 << 20;
+/// The most bytes read from all files in an archive together.
+#[cfg(any(feature = "zip", feature = "tar"))]
+const MAX_ARCHIVE_SIZE: u64 = 512 << 20;
+/// The bytes read from a file to tell whether it is text before reading the rest of it.
+#[cfg(any(feature = "zip", feature = "tar"))]
+const SNIFF_SIZE: u64 = 8 << 10;
+
+/// The formats of archives, told apart by the file extension.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    fn of(path: &Path) -> Result<Self, Error> {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        if name.ends_with(".zip") {
+            Ok(Self::Zip)
+        }
+        else if name.ends_with(".tar") {
+            Ok(Self::Tar)
+        }
+        else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        }
+        else {
+            Err(Error::new(ErrorKind::Unsupported, format!("{} is not a zip or tar archive", path.display())))
+        }
+    }
+}
+
+impl SourceCache {
+    /// Load every text file inside a `.zip`, `.tar` or `.tar.gz` archive without unpacking it, returns the loaded
+    /// sources in archive order.
+    ///
+    /// Each file is a [`SourcePath::Archive`], shown as `package.zip!/src/main.x`. Files are decoded like local files,
+    /// the ones that are not text are left out without reading them whole. Zip archives need the `zip` feature, tar
+    /// archives the `tar` feature.
+    ///
+    /// Archives are read as untrusted input, fails with [`ErrorKind::InvalidData`] if a file holds more than 64 MiB or
+    /// the files together more than 512 MiB, whatever sizes the archive claims.
+    pub fn load_archive<P>(&mut self, archive: P) -> Result<Vec<SourceID>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let archive = archive.as_ref();
+        let mut files = vec![];
+        for (entry, bytes) in read_entries(archive, false, |_| true)? {
+            if let Ok(mut source) = TextDecoder::default().decode(bytes) {
+                source.set_source(SourcePath::Archive { archive: archive.to_path_buf(), entry });
+                files.push(self.insert(source)?);
+            }
+        }
+        Ok(files)
+    }
+    /// Load a single file inside an archive, see [`SourceCache::load_archive`].
+    ///
+    /// Fails with [`ErrorKind::NotFound`] if the archive has no such file, and [`ErrorKind::InvalidData`] if it is not
+    /// text.
+    pub fn load_archived<P>(&mut self, archive: P, entry: &str) -> Result<SourceID, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let archive = archive.as_ref();
+        let entry = normalize(entry);
+        let bytes = match read_entries(archive, true, |name| name == entry)?.pop() {
+            Some((_, bytes)) => bytes,
+            None => return Err(Error::new(ErrorKind::NotFound, format!("{} not found in {}", entry, archive.display()))),
+        };
+        let mut source = TextDecoder::default().decode(bytes)?;
+        source.set_source(SourcePath::Archive { archive: archive.to_path_buf(), entry: entry.to_string() });
+        self.insert(source)
+    }
+}
+
+/// Strip the `./` and `/` that some archivers put in front of entry names.
+fn normalize(entry: &str) -> &str {
+    entry.trim_start_matches("./").trim_start_matches('/')
+}
+
+/// Reads the files of an archive into memory, within [`MAX_ENTRY_SIZE`] and [`MAX_ARCHIVE_SIZE`].
+#[cfg(any(feature = "zip", feature = "tar"))]
+struct EntryReader {
+    /// Whether files that are not text are read too
+    binary: bool,
+    total: u64,
+}
+
+#[cfg(any(feature = "zip", feature = "tar"))]
+impl EntryReader {
+    /// Read a file, gives `None` for a file that is not text if those are skipped.
+    fn read<R: Read>(&mut self, name: &str, mut entry: R) -> Result<Option<Vec<u8>>, Error> {
+        let mut bytes = vec![];
+        (&mut entry).take(SNIFF_SIZE).read_to_end(&mut bytes)?;
+        if !self.binary && TextDecoder::default().rejects_prefix(&bytes) {
+            return Ok(None);
+        }
+        entry.take(MAX_ENTRY_SIZE + 1 - bytes.len() as u64).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > MAX_ENTRY_SIZE {
+            let message = format!("{} is larger than {} bytes", name, MAX_ENTRY_SIZE);
+            return Err(Error::new(ErrorKind::InvalidData, message));
+        }
+        self.total += bytes.len() as u64;
+        if self.total > MAX_ARCHIVE_SIZE {
+            let message = format!("The files of the archive are larger than {} bytes together", MAX_ARCHIVE_SIZE);
+            return Err(Error::new(ErrorKind::InvalidData, message));
+        }
+        Ok(Some(bytes))
+    }
+}
+
+/// Read the contents of the regular files whose normalized names pass the filter, files that are not text are left out
+/// unless `binary` is set.
+#[cfg_attr(not(all(feature = "zip", feature = "tar")), allow(unused_variables))]
+fn read_entries<F>(archive: &Path, binary: bool, filter: F) -> Result<Vec<(String, Vec<u8>)>, Error>
+where
+    F: FnMut(&str) -> bool,
+{
+    let kind = ArchiveKind::of(archive)?;
+    #[cfg(any(feature = "zip", feature = "tar"))]
+    let reader = EntryReader { binary, total: 0 };
+    match kind {
+        #[cfg(feature = "zip")]
+        ArchiveKind::Zip => read_zip(File::open(archive)?, reader, filter),
+        #[cfg(feature = "tar")]
+        ArchiveKind::Tar => read_tar(File::open(archive)?, reader, filter),
+        #[cfg(feature = "tar")]
+        ArchiveKind::TarGz => read_tar(flate2::read::GzDecoder::new(File::open(archive)?), reader, filter),
+        #[allow(unreachable_patterns)]
+        _ => {
+            let feature = if kind == ArchiveKind::Zip { "zip" } else { "tar" };
+            Err(Error::new(ErrorKind::Unsupported, format!("Reading {:?} archives needs the `{}` feature", kind, feature)))
+        }
+    }
+}
+
+#[cfg(feature = "zip")]
+fn read_zip<F>(file: File, mut reader: EntryReader, mut filter: F) -> Result<Vec<(String, Vec<u8>)>, Error>
+where
+    F: FnMut(&str) -> bool,
+{
+    let mut zip = zip::ZipArchive::new(file)?;
+    let mut entries = vec![];
+    for index in 0..zip.len() {
+        let entry = zip.by_index(index)?;
+        let name = normalize(entry.name()).to_string();
+        if entry.is_file() && filter(&name) {
+            if let Some(bytes) = reader.read(&name, entry)? {
+                entries.push((name, bytes));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(feature = "tar")]
+fn read_tar<R, F>(archive: R, mut reader: EntryReader, mut filter: F) -> Result<Vec<(String, Vec<u8>)>, Error>
+where
+    R: Read,
+    F: FnMut(&str) -> bool,
+{
+    let mut entries = vec![];
+    for entry in tar::Archive::new(archive).entries()? {
+        let entry = entry?;
+        let name = normalize(&String::from_utf8_lossy(&entry.path_bytes())).to_string();
+        if entry.header().entry_type().is_file() && filter(&name) {
+            if let Some(bytes) = reader.read(&name, entry)? {
+                entries.push((name, bytes));
+            }
+        }
+    }
+    Ok(entries)
+}
@@ -0,0 +1,66 @@
+use super::*;
+use crate::SourceLine;
+use std::{io::ErrorKind, path::PathBuf, sync::OnceLock, time::SystemTime};
+
+/// A local file that is only read when first fetched, and must still have the content it had when registered unless it
+/// was evicted.
+#[derive(Clone, Debug)]
+pub(crate) struct DeferredSource {
+    /// Always a [`SourcePath::Local`]
+    pub path: SourcePath,
+    pub hash: u64,
+    /// Whether the file must still have `hash` when read, evicted sources take the file as it is then
+    pub verify: bool,
+    /// When the file was modified as of `hash`, if known
+    pub modified: Option<SystemTime>,
+    /// The line table computed when the source was registered, reused if it still fits
+    pub lines: Option<Box<[SourceLine]>>,
+    loaded: OnceLock<Result<Arc<SourceText>, (ErrorKind, String)>>,
+}
+
+impl DeferredSource {
+    pub fn new(path: PathBuf, hash: u64, lines: Option<Box<[SourceLine]>>) -> Self {
+        Self { path: SourcePath::Local(path), hash, verify: true, modified: None, lines, loaded: OnceLock::new() }
+    }
+    /// Drop the text of a local source, which is read again as the file is when next fetched.
+    pub fn evicted(source: &SourceText) -> Self {
+        Self {
+            path: source.get_source().clone(),
+            hash: source.get_content_hash(),
+            verify: false,
+            modified: source.get_modified(),
+            lines: None,
+            loaded: OnceLock::new(),
+        }
+    }
+    /// Forget the loaded text, so the file is read again when next fetched.
+    pub fn unloaded(&self) -> Self {
+        match self.get_loaded() {
+            Some(source) if !self.verify => Self::evicted(source),
+            _ => Self { loaded: OnceLock::new(), ..self.clone() },
+        }
+    }
+    /// Read the file once, later calls return the same text or error.
+    pub fn load(&self, vfs: &VirtualFileSystem) -> Result<&Arc<SourceText>, std::io::Error> {
+        let loaded = self.loaded.get_or_init(|| {
+            let path = match &self.path {
+                SourcePath::Local(path) => path,
+                _ => unreachable!("deferred sources are local"),
+            };
+            let mut source = vfs.read(path).map_err(|e| (e.kind(), e.to_string()))?;
+            if self.verify && source.get_content_hash() != self.hash {
+                let message = format!("File {} changed since it was registered", path.display());
+                return Err((ErrorKind::InvalidData, message));
+            }
+            if let Some(lines) = &self.lines {
+                source.restore_lines(lines.clone());
+            }
+            Ok(Arc::new(source))
+        });
+        loaded.as_ref().map_err(|(kind, message)| std::io::Error::new(*kind, message.as_str()))
+    }
+    /// Get the text if it was already loaded successfully.
+    pub fn get_loaded(&self) -> Option<&Arc<SourceText>> {
+        self.loaded.get()?.as_ref().ok()
+    }
+}
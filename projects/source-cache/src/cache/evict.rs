@@ -0,0 +1,136 @@
+use super::*;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+/// How much text a [`SourceCache`] holds in memory before it evicts sources, see [`SourceCache::set_capacity`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CacheCapacity {
+    /// The total length in bytes of the texts held
+    Bytes(usize),
+    /// The number of texts held
+    Entries(usize),
+}
+
+/// When each source was last inserted or fetched, so the least recently used ones are evicted first.
+#[derive(Debug, Default)]
+pub(crate) struct Recency {
+    clock: AtomicU64,
+    used: Mutex<HashMap<SourceID, u64>>,
+}
+
+impl CacheCapacity {
+    fn limit(&self) -> usize {
+        match self {
+            Self::Bytes(limit) | Self::Entries(limit) => *limit,
+        }
+    }
+    fn size(&self, source: &SourceText) -> usize {
+        match self {
+            Self::Bytes(_) => source.get_length(),
+            Self::Entries(_) => 1,
+        }
+    }
+}
+
+impl Recency {
+    pub fn touch(&self, file: SourceID) {
+        let now = self.clock.fetch_add(1, Ordering::Relaxed) + 1;
+        self.used.lock().expect("recency is poisoned").insert(file, now);
+    }
+    fn get(&mut self, file: &SourceID) -> u64 {
+        self.used.get_mut().expect("recency is poisoned").get(file).copied().unwrap_or(0)
+    }
+}
+
+impl Clone for Recency {
+    fn clone(&self) -> Self {
+        Self {
+            clock: AtomicU64::new(self.clock.load(Ordering::Relaxed)),
+            used: Mutex::new(self.used.lock().expect("recency is poisoned").clone()),
+        }
+    }
+}
+
+impl SourceCache {
+    /// Limit the text held in memory, evicting the least recently used sources once it is exceeded.
+    ///
+    /// Only texts read from local files are evicted, as they can be read again. Snippets, remote sources, texts given
+    /// a local path without being read from it, files with unsaved buffers and sources whose file could not be
+    /// refreshed are pinned. An evicted source keeps its [`SourceID`] and is read again
+    /// when fetched, with the content its file has then.
+    ///
+    /// The capacity is enforced whenever a source is inserted, texts read again by [`SourceCache::fetch`] count
+    /// towards it from the next insertion on.
+    ///
+    /// ```
+    /// # use source_cache::{CacheCapacity, SourceCache};
+    /// let mut cache = SourceCache::default().with_capacity(CacheCapacity::Entries(100));
+    /// let file = cache.load_text("let x = 1;", "main.x").unwrap();
+    /// assert_eq!(cache.fetch(&file).unwrap().text(), "let x = 1;");
+    /// ```
+    pub fn with_capacity(mut self, capacity: CacheCapacity) -> Self {
+        self.set_capacity(Some(capacity));
+        self
+    }
+    /// Limit the text held in memory, or remove the limit, see [`SourceCache::with_capacity`].
+    pub fn set_capacity(&mut self, capacity: Option<CacheCapacity>) {
+        self.capacity = capacity;
+        self.evict(None);
+    }
+    /// Get the limit of the text held in memory.
+    pub fn get_capacity(&self) -> Option<CacheCapacity> {
+        self.capacity
+    }
+    /// Evict the least recently used sources until the capacity is met, returns the evicted sources.
+    pub fn shrink_to_capacity(&mut self) -> Vec<SourceID> {
+        self.evict(None)
+    }
+    /// Evict down to the capacity, never evicting `keep`.
+    pub(super) fn evict(&mut self, keep: Option<SourceID>) -> Vec<SourceID> {
+        let Some(capacity) = self.capacity
+        else {
+            return vec![];
+        };
+        let loaded = self.deferred.values().filter_map(|d| d.get_loaded());
+        let mut total: usize = self.cache.values().chain(loaded).map(|s| capacity.size(s)).sum();
+        if total <= capacity.limit() {
+            return vec![];
+        }
+        let mut candidates = vec![];
+        for (file, source) in &self.cache {
+            // The text of a buffer is held by the file system anyway, and texts without a modification time were not
+            // read from the file
+            let SourcePath::Local(path) = source.get_source()
+            else {
+                continue;
+            };
+            if source.get_modified().is_some() && !source.is_dirty() && !self.vfs.has_buffer(path) {
+                candidates.push((*file, capacity.size(source)));
+            }
+        }
+        for (file, deferred) in &self.deferred {
+            if let Some(source) = deferred.get_loaded() {
+                candidates.push((*file, capacity.size(source)));
+            }
+        }
+        candidates.retain(|(file, _)| Some(*file) != keep);
+        candidates.sort_by_cached_key(|(file, _)| self.recency.get(file));
+        let mut evicted = vec![];
+        for (file, size) in candidates {
+            if total <= capacity.limit() {
+                break;
+            }
+            let deferred = match (self.cache.remove(&file), self.deferred.get(&file)) {
+                (Some(source), _) => DeferredSource::evicted(&source),
+                (None, Some(deferred)) => deferred.unloaded(),
+                (None, None) => continue,
+            };
+            self.deferred.insert(file, deferred);
+            total -= size;
+            evicted.push(file);
+        }
+        evicted
+    }
+}
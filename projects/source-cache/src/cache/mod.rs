@@ -1,11 +1,50 @@
-use crate::{SourceID, SourcePath, SourceText, Url};
-use std::{borrow::Cow, collections::HashMap, path::Path};
+use crate::{
+    loader::SourceLoaders, PathRemap, SourceID, SourceLoader, SourceMap, SourcePath, SourceSpan, SourceText, Url,
+    VirtualFileSystem,
+};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap},
+    path::Path,
+    sync::Arc,
+};
+mod deferred;
 mod display;
+mod evict;
+mod shared;
+#[cfg(feature = "serde")]
+mod snapshot;
+mod store;
+mod watch;
+
+use self::{
+    deferred::DeferredSource,
+    evict::Recency,
+    store::{check_collision, replace_source, SourceStore},
+};
+#[cfg(feature = "serde")]
+pub use self::snapshot::{SnapshotMode, SourceSnapshot};
+pub use self::{evict::CacheCapacity, shared::SharedSourceCache, watch::SourceWatcher};
 
 /// A [`Cache`] that fetches [`SourceText`]s from the filesystem.
+///
+/// Local files are resolved through a [`VirtualFileSystem`], which reads the real file system unless unsaved buffers or
+/// other layers are added to it.
+///
+/// Texts are held behind an [`Arc`], so cloning the cache or handing a text to another thread is cheap. See
+/// [`SharedSourceCache`] to load sources from many threads at once.
 #[derive(Default, Debug, Clone)]
 pub struct SourceCache {
-    cache: HashMap<SourceID, SourceText>,
+    cache: HashMap<SourceID, Arc<SourceText>>,
+    loaders: SourceLoaders,
+    vfs: VirtualFileSystem,
+    /// Source maps by the source they map from
+    maps: HashMap<SourceID, Arc<SourceMap>>,
+    /// Local files that are read when first fetched
+    deferred: HashMap<SourceID, DeferredSource>,
+    remap: PathRemap,
+    capacity: Option<CacheCapacity>,
+    recency: Recency,
 }
 
 impl SourceCache {
@@ -14,33 +53,127 @@ impl SourceCache {
     where
         P: AsRef<Path>,
     {
-        let path = path.as_ref();
-        let text = std::fs::read_to_string(&path)?;
-        let source = SourceText::from(text).with_path(path);
-        let name_hash = source.source_id();
-        self.cache.insert(name_hash, source);
-        Ok(name_hash)
+        self.store_local(path.as_ref())
     }
-    /// Create a new [`SourceCache`].
+    /// Load a local file through a memory map, lines are only indexed as far as diagnostics reference them.
+    ///
+    /// Prefer this over [`SourceCache::load_local`] when many files are loaded but few are reported on. The file is
+    /// always mapped from the real file system, bypassing the [`VirtualFileSystem`].
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is held by the cache, see [`SourceText::mapped`].
+    pub unsafe fn load_mapped<P>(&mut self, path: P) -> Result<SourceID, std::io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        let source = SourceText::mapped(path.as_ref())?;
+        self.insert(source)
+    }
+    /// Load the text behind the url with the loader registered for its scheme.
+    ///
+    /// `file://` urls are read through the [`VirtualFileSystem`] like [`SourceCache::load_local`], so they see
+    /// unsaved buffers and get the same [`SourceID`].
     pub fn load_remote(&mut self, url: Url) -> Result<SourceID, std::io::Error> {
-        let path = url.as_ref();
-        let text = std::fs::read_to_string(&path)?;
-        let source = SourceText::from(text).with_remote(url);
-        let name_hash = source.source_id();
-        self.cache.insert(name_hash, source);
-        Ok(name_hash)
+        let source = self.loaders.load_source(&self.vfs, url)?;
+        self.insert(source)
+    }
+
+    /// Register the loader used by [`SourceCache::load_remote`] for urls with the given scheme, returns the loader it
+    /// replaces.
+    ///
+    /// Loaders for `file` and `data` are registered by default.
+    pub fn add_loader<L>(&mut self, scheme: &str, loader: L) -> Option<Arc<dyn SourceLoader>>
+    where
+        L: SourceLoader + 'static,
+    {
+        self.loaders.insert(scheme, Arc::new(loader))
+    }
+    /// Remove the loader for urls with the given scheme.
+    pub fn remove_loader(&mut self, scheme: &str) -> Option<Arc<dyn SourceLoader>> {
+        self.loaders.remove(scheme)
+    }
+
+    /// Add or replace the unsaved buffer of a local file and load it, the source keeps the [`SourceID`] of the file.
+    pub fn open_buffer<P, S>(&mut self, path: P, text: S) -> Result<SourceID, std::io::Error>
+    where
+        P: AsRef<Path>,
+        S: Into<String>,
+    {
+        self.store_buffer(path.as_ref(), text.into())
+    }
+    /// Drop the unsaved buffer of a local file and load the file from the layers below again.
+    ///
+    /// If no layer has the file any more, the source is removed from the cache and the error is returned.
+    pub fn close_buffer<P>(&mut self, path: P) -> Result<SourceID, std::io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.drop_buffer(path.as_ref())
+    }
+    /// Get the file system local files are loaded from.
+    pub fn get_vfs(&self) -> &VirtualFileSystem {
+        &self.vfs
+    }
+    /// Replace the file system local files are loaded from, the sources already loaded are kept.
+    pub fn set_vfs(&mut self, vfs: VirtualFileSystem) {
+        self.vfs = vfs;
     }
 
     /// Create a new [`SourceCache`].
-    pub fn load_text<T, N>(&mut self, text: T, name: N) -> SourceID
+    ///
+    /// Fails if the name collides with the [`SourceID`] of another source, see [`SourceCache::insert`].
+    pub fn load_text<T, N>(&mut self, text: T, name: N) -> Result<SourceID, std::io::Error>
     where
         T: ToString,
         N: ToString,
     {
         let source = SourceText::snippet(text.to_string(), name.to_string());
+        self.insert(source)
+    }
+    /// Add a source to the cache under its [`SourceID`], replacing any source with the same path.
+    ///
+    /// Fails with [`ErrorKind::AlreadyExists`](std::io::ErrorKind::AlreadyExists) if a source with another path already
+    /// has the same id, instead of silently replacing it.
+    pub fn insert<S>(&mut self, source: S) -> Result<SourceID, std::io::Error>
+    where
+        S: Into<Arc<SourceText>>,
+    {
+        let source = source.into();
         let name_hash = source.source_id();
-        self.cache.insert(name_hash, source);
-        name_hash
+        if let Some(old) = self.deferred.get(&name_hash) {
+            check_collision(name_hash, &old.path, source.get_source())?;
+        }
+        match self.cache.entry(name_hash) {
+            Entry::Occupied(mut old) => replace_source(old.get_mut(), source)?,
+            Entry::Vacant(new) => {
+                new.insert(source);
+            }
+        }
+        self.deferred.remove(&name_hash);
+        self.recency.touch(name_hash);
+        self.evict(Some(name_hash));
+        Ok(name_hash)
+    }
+    /// Register a local file that is only read when it is first fetched, and must then have the given content hash.
+    ///
+    /// Fetching fails with [`ErrorKind::InvalidData`](std::io::ErrorKind::InvalidData) if the content changed, so
+    /// diagnostics are never shown against another text than they were made for, until the source is reloaded by
+    /// [`SourceCache::refresh`]. See [`SourceText::get_content_hash`].
+    pub fn load_deferred<P>(&mut self, path: P, content_hash: u64) -> Result<SourceID, std::io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.insert_deferred(DeferredSource::new(path.as_ref().to_path_buf(), content_hash, None))
+    }
+    fn insert_deferred(&mut self, source: DeferredSource) -> Result<SourceID, std::io::Error> {
+        let name_hash = source.path.source_id();
+        if let Some(old) = self.cache.get(&name_hash) {
+            check_collision(name_hash, old.get_source(), &source.path)?;
+        }
+        self.cache.remove(&name_hash);
+        self.deferred.insert(name_hash, source);
+        Ok(name_hash)
     }
     /// Set the file identifier buy not update the context
     pub unsafe fn set_source<N>(&mut self, file: SourceID, source: N) -> bool
@@ -49,7 +182,7 @@ impl SourceCache {
     {
         match self.cache.get_mut(&file) {
             Some(s) => {
-                s.set_source(SourcePath::Snippet(source.into()));
+                Arc::make_mut(s).set_source(SourcePath::Snippet(source.into()));
                 true
             }
             None => false,
@@ -57,13 +190,213 @@ impl SourceCache {
     }
     /// Create a new [`SourceCache`].
     pub fn fetch(&self, file: &SourceID) -> Result<&SourceText, std::io::Error> {
-        match self.cache.get(file) {
-            Some(source) => Ok(source),
-            None => Err(std::io::Error::new(std::io::ErrorKind::NotFound, format!("File {:?} not found", file))),
+        Ok(self.fetch_arc(file)?)
+    }
+    /// Get a shared handle to the source, which can be sent to other threads.
+    pub fn fetch_shared(&self, file: &SourceID) -> Result<Arc<SourceText>, std::io::Error> {
+        Ok(self.fetch_arc(file)?.clone())
+    }
+    fn fetch_arc(&self, file: &SourceID) -> Result<&Arc<SourceText>, std::io::Error> {
+        if self.capacity.is_some() {
+            self.recency.touch(*file);
+        }
+        match (self.cache.get(file), self.deferred.get(file)) {
+            (Some(source), _) => Ok(source),
+            (None, Some(deferred)) => deferred.load(&self.vfs),
+            (None, None) => Err(not_found(file)),
         }
     }
     /// Create a new [`SourceCache`].
     pub fn source_path(&self, file: &SourceID) -> Option<&SourcePath> {
-        Some(&self.cache.get(file)?.get_source())
+        match (self.cache.get(file), self.deferred.get(file)) {
+            (Some(source), _) => Some(source.get_source()),
+            (None, Some(deferred)) => Some(&deferred.path),
+            (None, None) => None,
+        }
+    }
+
+    /// Get the path of the source as it should be shown to users, rewritten by the [`PathRemap`] of the cache.
+    ///
+    /// Embedded sources are shown by the path of the source they are embedded in.
+    pub fn display_path(&self, file: &SourceID) -> Option<String> {
+        match self.source_path(file)? {
+            SourcePath::Embedded(region) if region.file != *file => self.display_path(&region.file),
+            path => Some(self.remap.display(path)),
+        }
+    }
+    /// Get the rules used to show paths, see [`SourceCache::display_path`].
+    pub fn get_path_remap(&self) -> &PathRemap {
+        &self.remap
+    }
+    /// Set the rules used to show paths, for example to show paths relative to the workspace root.
+    pub fn set_path_remap(&mut self, remap: PathRemap) {
+        self.remap = remap;
+    }
+
+    /// Register a region of a source as a source of its own, such as a code block in a Markdown file or a query in a
+    /// string literal, so a parser of the embedded language can report offsets relative to the region.
+    ///
+    /// The text of the new source is the text of the region, which must be copied verbatim, use a [`SourceMap`] for
+    /// content that is unescaped. Spans in it are moved to the outer source by [`SourceCache::resolve_embedded`].
+    ///
+    /// Fails with [`ErrorKind::InvalidInput`](std::io::ErrorKind::InvalidInput) if the region is not in its source.
+    ///
+    /// ```
+    /// # use source_cache::SourceCache;
+    /// let mut cache = SourceCache::default();
+    /// let doc = cache.load_text("Run:\n```sql\nSELECT 1\n```\n", "readme.md").unwrap();
+    /// let sql = cache.add_embedded(doc.with_range(12..20)).unwrap();
+    /// assert_eq!(cache.fetch(&sql).unwrap().text(), "SELECT 1");
+    /// assert_eq!(cache.resolve_embedded(sql.with_range(7..8)), doc.with_range(19..20));
+    /// ```
+    pub fn add_embedded(&mut self, region: SourceSpan) -> Result<SourceID, std::io::Error> {
+        let parent = self.fetch(&region.file)?;
+        let text = match region.text(parent) {
+            Some(text) => text.to_string(),
+            None => {
+                let message = format!("Region {:?} is not in the source", region);
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+            }
+        };
+        let mut source = SourceText::from(text);
+        source.set_source(SourcePath::Embedded(region));
+        self.insert(source)
+    }
+    /// Move a span from embedded sources to the source they are embedded in, through any number of nested regions.
+    ///
+    /// Spans in other sources are returned as they are.
+    pub fn resolve_embedded(&self, mut span: SourceSpan) -> SourceSpan {
+        // Bounded to stop on cycles
+        for _ in 0..16 {
+            match self.source_path(&span.file) {
+                Some(SourcePath::Embedded(region)) => {
+                    span = SourceSpan { start: region.start + span.start, end: region.start + span.end, file: region.file }
+                }
+                _ => break,
+            }
+        }
+        span
+    }
+
+    /// Register a source map for its generated source, returns the map it replaces.
+    pub fn add_source_map(&mut self, map: SourceMap) -> Option<Arc<SourceMap>> {
+        self.maps.insert(map.get_generated(), Arc::new(map))
+    }
+    /// Remove the source map of the generated source.
+    pub fn remove_source_map(&mut self, generated: &SourceID) -> Option<Arc<SourceMap>> {
+        self.maps.remove(generated)
+    }
+    /// Get the source map of the generated source.
+    pub fn get_source_map(&self, generated: &SourceID) -> Option<&SourceMap> {
+        Some(self.maps.get(generated)?)
+    }
+    /// Map a span of generated code back to the original source, following maps of code generated from generated code.
+    ///
+    /// Returns `None` if the span is not in a generated source, or the generated code there has no original.
+    pub fn map_span(&self, span: SourceSpan) -> Option<SourceSpan> {
+        let mut mapped = self.get_source_map(&span.file)?.map_span(span)?;
+        // Bounded to stop on cycles
+        for _ in 0..16 {
+            match self.get_source_map(&mapped.file).and_then(|map| map.map_span(mapped)) {
+                Some(next) => mapped = next,
+                None => break,
+            }
+        }
+        Some(mapped)
+    }
+
+    /// Check whether the source is known to be out of date with its file.
+    pub fn is_stale(&self, file: &SourceID) -> bool {
+        self.cache.get(file).is_some_and(|s| s.is_dirty())
+    }
+    /// Reload every local source whose file changed since it was read, returns the sources that changed.
+    ///
+    /// A source whose file can no longer be read is kept as it was but marked stale, see [`SourceCache::is_stale`].
+    /// Deferred and evicted sources whose file changed are read again, see [`SourceCache::refresh_source`].
+    pub fn refresh(&mut self) -> Vec<SourceID> {
+        let files: Vec<SourceID> = self.cache.keys().chain(self.deferred.keys()).copied().collect();
+        self.refresh_sources(&files)
+    }
+    /// Reload the given local sources if their files changed since they were read, returns the sources that changed.
+    pub fn refresh_sources(&mut self, files: &[SourceID]) -> Vec<SourceID> {
+        files.iter().filter(|file| self.refresh_source(file)).copied().collect()
+    }
+    /// Reload a local source if its file changed since it was read, returns `true` if the source changed.
+    ///
+    /// The modification time is checked first, the file is only read when it differs, and the text is only replaced
+    /// when the content hash differs. Memory-mapped sources are reloaded into memory. Files are read through the
+    /// [`VirtualFileSystem`], so a source with an unsaved buffer follows the buffer rather than the file.
+    ///
+    /// A deferred or evicted source whose file changed is read into memory again, also when
+    /// [`SourceCache::load_deferred`] would refuse the new content.
+    pub fn refresh_source(&mut self, file: &SourceID) -> bool {
+        let source = match self.cache.get_mut(file) {
+            Some(s) => s,
+            None => return self.refresh_deferred(file),
+        };
+        let path = match source.get_source() {
+            SourcePath::Local(path) => path.clone(),
+            _ => return false,
+        };
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified == source.get_modified() && !source.is_dirty() {
+            return false;
+        }
+        match self.vfs.read(&path) {
+            Ok(new) => {
+                let changed = source.is_dirty() || new.get_content_hash() != source.get_content_hash();
+                *source = Arc::new(new);
+                changed
+            }
+            Err(_) => {
+                let changed = !source.is_dirty();
+                Arc::make_mut(source).set_dirty(true);
+                changed
+            }
+        }
+    }
+    fn refresh_deferred(&mut self, file: &SourceID) -> bool {
+        let Some(deferred) = self.deferred.get(file)
+        else {
+            return false;
+        };
+        let SourcePath::Local(path) = &deferred.path
+        else {
+            return false;
+        };
+        let (hash, known) = match deferred.get_loaded() {
+            Some(source) => (source.get_content_hash(), source.get_modified()),
+            None => (deferred.hash, deferred.modified),
+        };
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified == known {
+            return false;
+        }
+        match self.vfs.read(path) {
+            Ok(new) if new.get_content_hash() != hash => {
+                self.deferred.remove(file);
+                self.cache.insert(*file, Arc::new(new));
+                self.recency.touch(*file);
+                self.evict(Some(*file));
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn not_found(file: &SourceID) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::NotFound, format!("File {:?} not found", file))
+}
+
+impl SourceStore for SourceCache {
+    fn vfs(&self) -> &VirtualFileSystem {
+        &self.vfs
+    }
+    fn store(&mut self, source: Arc<SourceText>) -> Result<SourceID, std::io::Error> {
+        self.insert(source)
+    }
+    fn forget(&mut self, file: &SourceID) {
+        self.cache.remove(file);
     }
 }
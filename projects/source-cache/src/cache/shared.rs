@@ -0,0 +1,176 @@
+use super::*;
+use dashmap::{mapref::entry::Entry, DashMap};
+use std::sync::RwLock;
+
+/// A [`SourceCache`] that can be shared between threads, usually through an [`Arc`].
+///
+/// Every method takes `&self`, so files can be loaded in parallel, and fetching hands out [`Arc`] handles that stay
+/// valid even if the source is replaced later. Use [`SharedSourceCache::snapshot`] to render diagnostics from the
+/// current state on any thread.
+#[derive(Debug, Default)]
+pub struct SharedSourceCache {
+    cache: DashMap<SourceID, Arc<SourceText>>,
+    loaders: RwLock<SourceLoaders>,
+    vfs: VirtualFileSystem,
+    maps: DashMap<SourceID, Arc<SourceMap>>,
+    remap: RwLock<PathRemap>,
+}
+
+impl SharedSourceCache {
+    /// Load a local file, see [`SourceCache::load_local`].
+    pub fn load_local<P>(&self, path: P) -> Result<SourceID, std::io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        SourceStore::store_local(&mut { self }, path.as_ref())
+    }
+    /// Load many local files in parallel, on as many threads as the machine has cores.
+    ///
+    /// The results are in the same order as the paths.
+    pub fn load_locals<P>(&self, paths: &[P]) -> Vec<Result<SourceID, std::io::Error>>
+    where
+        P: AsRef<Path> + Sync,
+    {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = paths.len().div_ceil(threads).max(1);
+        std::thread::scope(|scope| {
+            let tasks: Vec<_> = paths
+                .chunks(chunk)
+                .map(|paths| scope.spawn(move || paths.iter().map(|path| self.load_local(path)).collect::<Vec<_>>()))
+                .collect();
+            tasks.into_iter().flat_map(|task| task.join().expect("loading thread panicked")).collect()
+        })
+    }
+    /// Load a local file through a memory map, see [`SourceCache::load_mapped`].
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it is held by the cache, see [`SourceText::mapped`].
+    pub unsafe fn load_mapped<P>(&self, path: P) -> Result<SourceID, std::io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        self.insert(SourceText::mapped(path.as_ref())?)
+    }
+    /// Load the text behind the url, see [`SourceCache::load_remote`].
+    pub fn load_remote(&self, url: Url) -> Result<SourceID, std::io::Error> {
+        let source = self.loaders.read().expect("loaders are poisoned").load_source(&self.vfs, url)?;
+        self.insert(source)
+    }
+    /// Register the loader for urls with the given scheme, see [`SourceCache::add_loader`].
+    pub fn add_loader<L>(&self, scheme: &str, loader: L) -> Option<Arc<dyn SourceLoader>>
+    where
+        L: SourceLoader + 'static,
+    {
+        self.loaders.write().expect("loaders are poisoned").insert(scheme, Arc::new(loader))
+    }
+    /// Add or replace the unsaved buffer of a local file and load it, see [`SourceCache::open_buffer`].
+    pub fn open_buffer<P, S>(&self, path: P, text: S) -> Result<SourceID, std::io::Error>
+    where
+        P: AsRef<Path>,
+        S: Into<String>,
+    {
+        SourceStore::store_buffer(&mut { self }, path.as_ref(), text.into())
+    }
+    /// Drop the unsaved buffer of a local file and load the file from the layers below again, see
+    /// [`SourceCache::close_buffer`].
+    pub fn close_buffer<P>(&self, path: P) -> Result<SourceID, std::io::Error>
+    where
+        P: AsRef<Path>,
+    {
+        SourceStore::drop_buffer(&mut { self }, path.as_ref())
+    }
+    /// Get the file system local files are loaded from, see [`SourceCache::get_vfs`].
+    pub fn get_vfs(&self) -> &VirtualFileSystem {
+        &self.vfs
+    }
+    /// Load a snippet of text, see [`SourceCache::load_text`].
+    pub fn load_text<T, N>(&self, text: T, name: N) -> Result<SourceID, std::io::Error>
+    where
+        T: ToString,
+        N: ToString,
+    {
+        self.insert(SourceText::snippet(text.to_string(), name.to_string()))
+    }
+    /// Add a source to the cache under its [`SourceID`], see [`SourceCache::insert`].
+    pub fn insert<S>(&self, source: S) -> Result<SourceID, std::io::Error>
+    where
+        S: Into<Arc<SourceText>>,
+    {
+        let source = source.into();
+        let name_hash = source.source_id();
+        match self.cache.entry(name_hash) {
+            Entry::Occupied(mut old) => replace_source(old.get_mut(), source)?,
+            Entry::Vacant(new) => {
+                new.insert(source);
+            }
+        }
+        Ok(name_hash)
+    }
+    /// Get a shared handle to the source.
+    pub fn fetch(&self, file: &SourceID) -> Result<Arc<SourceText>, std::io::Error> {
+        match self.cache.get(file) {
+            Some(source) => Ok(source.clone()),
+            None => Err(not_found(file)),
+        }
+    }
+    /// Register a source map for its generated source, see [`SourceCache::add_source_map`].
+    pub fn add_source_map(&self, map: SourceMap) -> Option<Arc<SourceMap>> {
+        self.maps.insert(map.get_generated(), Arc::new(map))
+    }
+    /// Get the path of the source as it should be shown to users, see [`SourceCache::display_path`].
+    pub fn display_path(&self, file: &SourceID) -> Option<String> {
+        let source = self.cache.get(file)?.clone();
+        match source.get_source() {
+            SourcePath::Embedded(region) if region.file != *file => self.display_path(&region.file),
+            path => Some(self.remap.read().expect("path remap is poisoned").display(path)),
+        }
+    }
+    /// Get the rules used to show paths, see [`SourceCache::get_path_remap`].
+    pub fn get_path_remap(&self) -> PathRemap {
+        self.remap.read().expect("path remap is poisoned").clone()
+    }
+    /// Set the rules used to show paths, see [`SourceCache::set_path_remap`].
+    pub fn set_path_remap(&self, remap: PathRemap) {
+        *self.remap.write().expect("path remap is poisoned") = remap;
+    }
+    /// Get the path of the source.
+    pub fn source_path(&self, file: &SourceID) -> Option<SourcePath> {
+        Some(self.cache.get(file)?.get_source().clone())
+    }
+    /// Copy the current sources into a [`SourceCache`], which only clones the handles and not the texts.
+    pub fn snapshot(&self) -> SourceCache {
+        let loaders = self.loaders.read().expect("loaders are poisoned").clone();
+        let cache = self.cache.iter().map(|e| (*e.key(), e.value().clone())).collect();
+        let maps = self.maps.iter().map(|e| (*e.key(), e.value().clone())).collect();
+        let remap = self.get_path_remap();
+        SourceCache { cache, loaders, vfs: self.vfs.clone(), maps, remap, ..SourceCache::default() }
+    }
+}
+
+/// Deferred sources are read right away, the ones that can not be loaded any more are left out.
+impl From<SourceCache> for SharedSourceCache {
+    fn from(cache: SourceCache) -> Self {
+        let deferred: Vec<_> =
+            cache.deferred.iter().filter_map(|(id, source)| Some((*id, source.load(&cache.vfs).ok()?.clone()))).collect();
+        Self {
+            cache: cache.cache.into_iter().chain(deferred).collect(),
+            loaders: RwLock::new(cache.loaders),
+            vfs: cache.vfs,
+            maps: cache.maps.into_iter().collect(),
+            remap: RwLock::new(cache.remap),
+        }
+    }
+}
+
+impl SourceStore for &SharedSourceCache {
+    fn vfs(&self) -> &VirtualFileSystem {
+        &self.vfs
+    }
+    fn store(&mut self, source: Arc<SourceText>) -> Result<SourceID, std::io::Error> {
+        self.insert(source)
+    }
+    fn forget(&mut self, file: &SourceID) {
+        self.cache.remove(file);
+    }
+}
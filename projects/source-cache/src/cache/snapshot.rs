@@ -0,0 +1,102 @@
+use super::*;
+use crate::SourceLine;
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+
+/// A copy of the sources in a [`SourceCache`] that can be serialized, to replay diagnostics in a later run against the
+/// exact texts they were made for.
+///
+/// ```
+/// # use source_cache::{SnapshotMode, SourceCache};
+/// let mut cache = SourceCache::default();
+/// let file = cache.load_text("let x = 1;", "main.x").unwrap();
+/// let snapshot = cache.to_snapshot(SnapshotMode::Contents);
+/// let restored = SourceCache::from_snapshot(snapshot).unwrap();
+/// assert_eq!(restored.fetch(&file).unwrap().text(), "let x = 1;");
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SourceSnapshot {
+    sources: Vec<SnapshotSource>,
+}
+
+/// What a [`SourceSnapshot`] stores of local files.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SnapshotMode {
+    /// Store the text of every source.
+    #[default]
+    Contents,
+    /// Store only the content hash of local files, which are read again when first fetched from the restored cache and
+    /// must not have changed, see [`SourceCache::load_deferred`]. Other sources keep their text.
+    Hashes,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct SnapshotSource {
+    path: SourcePath,
+    hash: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    lines: Vec<SourceLine>,
+}
+
+impl SourceCache {
+    /// Copy the sources, with their line tables, into a [`SourceSnapshot`].
+    ///
+    /// Deferred sources that were never fetched are stored as hashes in either mode.
+    pub fn to_snapshot(&self, mode: SnapshotMode) -> SourceSnapshot {
+        let mut sources = Vec::with_capacity(self.cache.len() + self.deferred.len());
+        for source in self.cache.values() {
+            let text = match (mode, source.get_source()) {
+                (SnapshotMode::Hashes, SourcePath::Local(_)) => None,
+                _ => Some(source.text().to_string()),
+            };
+            sources.push(SnapshotSource {
+                path: source.get_source().clone(),
+                hash: source.get_content_hash(),
+                text,
+                lines: source.lines().cloned().collect(),
+            })
+        }
+        for deferred in self.deferred.values() {
+            let hash = deferred.get_loaded().map_or(deferred.hash, |source| source.get_content_hash());
+            let (text, lines) = match (mode, deferred.get_loaded()) {
+                (SnapshotMode::Contents, Some(source)) => (Some(source.text().to_string()), source.lines().cloned().collect()),
+                _ => (None, deferred.lines.iter().flat_map(|l| l.iter().cloned()).collect()),
+            };
+            sources.push(SnapshotSource { path: deferred.path.clone(), hash, text, lines })
+        }
+        SourceSnapshot { sources }
+    }
+    /// Restore the sources of a [`SourceSnapshot`] into a new cache, with the default loaders and file system.
+    ///
+    /// Fails with [`ErrorKind::InvalidData`] if a stored text does not match its hash, or a source without text is not a
+    /// local file.
+    pub fn from_snapshot(snapshot: SourceSnapshot) -> Result<Self, Error> {
+        let mut cache = Self::default();
+        for SnapshotSource { path, hash, text, lines } in snapshot.sources {
+            let lines = Some(lines.into_boxed_slice()).filter(|l| !l.is_empty());
+            match (text, path) {
+                (Some(text), path) => {
+                    let mut source = SourceText::from(text);
+                    source.set_source(path);
+                    if source.get_content_hash() != hash {
+                        let message = format!("Text of {:?} does not match its hash", source.get_source());
+                        return Err(Error::new(ErrorKind::InvalidData, message));
+                    }
+                    if let Some(lines) = lines {
+                        source.restore_lines(lines);
+                    }
+                    cache.insert(source)?;
+                }
+                (None, SourcePath::Local(path)) => {
+                    cache.insert_deferred(DeferredSource::new(path, hash, lines))?;
+                }
+                (None, path) => {
+                    return Err(Error::new(ErrorKind::InvalidData, format!("Source {:?} has no text", path)));
+                }
+            }
+        }
+        Ok(cache)
+    }
+}
@@ -0,0 +1,42 @@
+use super::*;
+
+/// The loading logic shared by [`SourceCache`] and [`SharedSourceCache`], written against the few operations their
+/// storage differs in.
+///
+/// [`SharedSourceCache`] loads through shared references, so it implements this for `&SharedSourceCache`.
+pub(super) trait SourceStore {
+    /// Get the file system local files are read from.
+    fn vfs(&self) -> &VirtualFileSystem;
+    /// Add a source under its [`SourceID`], see [`replace_source`].
+    fn store(&mut self, source: Arc<SourceText>) -> Result<SourceID, std::io::Error>;
+    /// Remove a source from the cache.
+    fn forget(&mut self, file: &SourceID);
+
+    fn store_local(&mut self, path: &Path) -> Result<SourceID, std::io::Error> {
+        let source = self.vfs().read(path)?;
+        self.store(Arc::new(source))
+    }
+    fn store_buffer(&mut self, path: &Path, text: String) -> Result<SourceID, std::io::Error> {
+        self.vfs().open_buffer(path, text);
+        self.store_local(path)
+    }
+    fn drop_buffer(&mut self, path: &Path) -> Result<SourceID, std::io::Error> {
+        self.vfs().close_buffer(path);
+        self.store_local(path).inspect_err(|_| self.forget(&SourcePath::Local(path.to_path_buf()).source_id()))
+    }
+}
+
+/// Replace the source in a slot of the cache, unless the new source has another path whose id collides.
+pub(super) fn replace_source(slot: &mut Arc<SourceText>, source: Arc<SourceText>) -> Result<(), std::io::Error> {
+    check_collision(source.source_id(), slot.get_source(), source.get_source())?;
+    *slot = source;
+    Ok(())
+}
+
+pub(super) fn check_collision(file: SourceID, old: &SourcePath, new: &SourcePath) -> Result<(), std::io::Error> {
+    if old == new {
+        return Ok(());
+    }
+    let message = format!("File {:?} collides with {:?} as {:?}", new, old, file);
+    Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, message))
+}
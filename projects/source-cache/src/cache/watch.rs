@@ -0,0 +1,114 @@
+use super::*;
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+    thread::JoinHandle,
+    time::{Duration, SystemTime},
+};
+
+/// Polls the files behind local sources on a background thread and reports the ones that changed.
+///
+/// The watcher only reports changes, pass them to [`SourceCache::refresh_sources`] to reload the text.
+///
+/// ```no_run
+/// # use source_cache::{SourceCache, SourceWatcher};
+/// # use std::time::Duration;
+/// let mut cache = SourceCache::default();
+/// cache.load_local("main.x").unwrap();
+/// let watcher = SourceWatcher::new(&cache, Duration::from_millis(200));
+/// loop {
+///     let changed = watcher.wait(Duration::from_secs(1));
+///     for file in cache.refresh_sources(&changed) {
+///         println!("{} changed", file);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct SourceWatcher {
+    changes: Receiver<SourceID>,
+    paths: Option<Sender<PathBuf>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl SourceWatcher {
+    /// Start watching every local source in the cache, checking the files every `interval`.
+    pub fn new(cache: &SourceCache, interval: Duration) -> Self {
+        let mut files: HashMap<PathBuf, Option<SystemTime>> = HashMap::new();
+        for source in cache.cache.values() {
+            if let SourcePath::Local(path) = source.get_source() {
+                files.insert(path.clone(), source.get_modified());
+            }
+        }
+        let (paths, new_paths) = channel();
+        let (changed, changes) = channel();
+        let thread = std::thread::spawn(move || poll(files, new_paths, changed, interval));
+        Self { changes, paths: Some(paths), thread: Some(thread) }
+    }
+    /// Start watching another file, which is reported once it changes from its current state.
+    pub fn watch<P>(&self, path: P)
+    where
+        P: AsRef<Path>,
+    {
+        if let Some(paths) = &self.paths {
+            paths.send(path.as_ref().to_path_buf()).ok();
+        }
+    }
+    /// Take the sources that changed since the last call, without blocking.
+    pub fn changed(&self) -> Vec<SourceID> {
+        let mut seen = HashSet::new();
+        self.changes.try_iter().filter(|file| seen.insert(*file)).collect()
+    }
+    /// Block until at least one source changed or the timeout elapses, then take the sources that changed.
+    pub fn wait(&self, timeout: Duration) -> Vec<SourceID> {
+        match self.changes.recv_timeout(timeout) {
+            Ok(first) => {
+                let mut changed = self.changed();
+                if !changed.contains(&first) {
+                    changed.insert(0, first);
+                }
+                changed
+            }
+            Err(_) => vec![],
+        }
+    }
+}
+
+impl Drop for SourceWatcher {
+    fn drop(&mut self) {
+        // Disconnecting the channel stops the polling thread
+        self.paths.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn poll(
+    mut files: HashMap<PathBuf, Option<SystemTime>>,
+    paths: Receiver<PathBuf>,
+    changed: Sender<SourceID>,
+    interval: Duration,
+) {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    loop {
+        match paths.recv_timeout(interval) {
+            Ok(path) => {
+                let time = modified(&path);
+                files.insert(path, time);
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        for (path, last) in files.iter_mut() {
+            let time = modified(path);
+            if time != *last {
+                *last = time;
+                if changed.send(SourcePath::Local(path.clone()).source_id()).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
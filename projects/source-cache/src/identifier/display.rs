@@ -10,6 +10,11 @@ impl Display for SourcePath {
                 Err(_) => f.write_str(&s.to_string_lossy()),
             },
             Self::Remote(s) => f.write_str(s.as_str()),
+            Self::Embedded(s) => write!(f, "{}[{}..{}]", s.file, s.start, s.end),
+            Self::NotebookCell { notebook, cell } => {
+                write!(f, "{}, cell [{}]", SourcePath::Local(notebook.clone()), cell + 1)
+            }
+            Self::Archive { archive, entry } => write!(f, "{}!/{}", SourcePath::Local(archive.clone()), entry),
         }
     }
 }
@@ -25,3 +30,33 @@ impl Display for SourceID {
         Debug::fmt(self, f)
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SourceID {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:016x}", self.hash))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SourceID {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = SourceID;
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a hexadecimal source id")
+            }
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                match u64::from_str_radix(v, 16) {
+                    Ok(hash) => Ok(SourceID { hash }),
+                    Err(e) => Err(E::custom(e)),
+                }
+            }
+            fn visit_u64<E: serde::de::Error>(self, hash: u64) -> Result<Self::Value, E> {
+                Ok(SourceID { hash })
+            }
+        }
+        deserializer.deserialize_str(Visitor)
+    }
+}
@@ -2,13 +2,16 @@ use crate::SourceSpan;
 use std::{
     borrow::Cow,
     fmt::{Debug, Display, Formatter},
-    hash::{DefaultHasher, Hash, Hasher},
+    hash::{Hash, Hasher},
     ops::Range,
     path::PathBuf,
 };
 use url::Url;
 
 mod display;
+mod remap;
+
+pub use self::remap::PathRemap;
 
 /// The source path
 #[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
@@ -23,20 +26,106 @@ pub enum SourcePath {
     Local(PathBuf),
     /// This is a remote identifier
     Remote(Url),
+    /// A region of another source, see [`SourceCache::add_embedded`](crate::SourceCache::add_embedded)
+    Embedded(SourceSpan),
+    /// A cell of a Jupyter notebook, by its zero-based position among all cells of the notebook
+    NotebookCell {
+        /// The path of the `.ipynb` file
+        notebook: PathBuf,
+        /// The zero-based position of the cell
+        cell: u32,
+    },
+    /// A file inside a `.zip`, `.tar` or `.tar.gz` archive, see
+    /// [`SourceCache::load_archive`](crate::SourceCache::load_archive)
+    Archive {
+        /// The path of the archive
+        archive: PathBuf,
+        /// The path of the file in the archive, with `/` separators
+        entry: String,
+    },
 }
 
 /// A type representing a single line of a [`Source`].
+///
+/// The id is a stable hash of the [`SourcePath`], the same path gives the same id across runs and versions of this crate,
+/// so it can be persisted. Local paths are hashed as the platform encodes them, with its separators, so ids of local
+/// files only match between machines of the same platform. With the `serde` feature it is serialized as a 16 digit
+/// hexadecimal string.
 #[derive(Copy, Clone, Default, Eq, PartialEq, Hash)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SourceID {
     pub(crate) hash: u64,
 }
 
+/// The 64-bit [FNV-1a](http://www.isthe.com/chongo/tech/comp/fnv/) hash, whose output is fixed by its specification,
+/// unlike [`std::hash::DefaultHasher`].
+///
+/// Only feed it with [`Hasher::write`], the [`Hash`] implementations of the standard library are not stable either.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct StableHasher {
+    state: u64,
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self { state: 0xCBF2_9CE4_8422_2325 }
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.state
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u64;
+            self.state = self.state.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+}
+
 impl SourcePath {
     /// Calculate the file from the identifier
+    ///
+    /// This is the FNV-1a hash of a tag byte for the kind of path (`0` anonymous, `1` snippet, `2` local, `3` remote,
+    /// `4` embedded, `5` notebook cell, `6` archive) followed by the name, the bytes of the path as given by
+    /// [`OsStr::as_encoded_bytes`](std::ffi::OsStr), the serialized url, the little endian id, start and end of the
+    /// region, the little endian cell position followed by the bytes of the notebook path, or the little endian 64-bit
+    /// length and bytes of the archive path followed by the entry.
     pub fn source_id(&self) -> SourceID {
-        let mut hasher = DefaultHasher::new();
-        self.hash(&mut hasher);
+        let mut hasher = StableHasher::default();
+        match self {
+            Self::Anonymous => hasher.write(&[0]),
+            Self::Snippet(name) => {
+                hasher.write(&[1]);
+                hasher.write(name.as_bytes());
+            }
+            Self::Local(path) => {
+                hasher.write(&[2]);
+                hasher.write(path.as_os_str().as_encoded_bytes());
+            }
+            Self::Remote(url) => {
+                hasher.write(&[3]);
+                hasher.write(url.as_str().as_bytes());
+            }
+            Self::Embedded(region) => {
+                hasher.write(&[4]);
+                hasher.write(&region.file.hash.to_le_bytes());
+                hasher.write(&region.start.to_le_bytes());
+                hasher.write(&region.end.to_le_bytes());
+            }
+            Self::NotebookCell { notebook, cell } => {
+                hasher.write(&[5]);
+                hasher.write(&cell.to_le_bytes());
+                hasher.write(notebook.as_os_str().as_encoded_bytes());
+            }
+            Self::Archive { archive, entry } => {
+                let archive = archive.as_os_str().as_encoded_bytes();
+                hasher.write(&[6]);
+                hasher.write(&(archive.len() as u64).to_le_bytes());
+                hasher.write(archive);
+                hasher.write(entry.as_bytes());
+            }
+        }
         SourceID { hash: hasher.finish() }
     }
 }
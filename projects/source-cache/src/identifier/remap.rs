@@ -0,0 +1,93 @@
+use super::*;
+use std::path::Path;
+
+/// Rewrites local paths for display, so rendered diagnostics do not depend on where the files are on a machine.
+///
+/// Prefix rules work like rustc's `--remap-path-prefix`, the last rule added that matches a path wins. Paths no rule
+/// matches are shown relative to the workspace root if they are under it.
+///
+/// ```
+/// # use source_cache::{PathRemap, SourcePath};
+/// let remap = PathRemap::default()
+///     .with_root("/runner/work/app")
+///     .with_prefix("/home/ci/.cargo/registry", "$CARGO");
+/// let path = |p: &str| SourcePath::Local(p.into());
+/// assert_eq!(remap.display(&path("/runner/work/app/src/main.rs")), "src/main.rs");
+/// assert_eq!(
+///     remap.display(&path("/home/ci/.cargo/registry/url-2.5.0/src/lib.rs")),
+///     "$CARGO/url-2.5.0/src/lib.rs"
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathRemap {
+    root: Option<PathBuf>,
+    prefixes: Vec<(PathBuf, PathBuf)>,
+}
+
+impl PathRemap {
+    /// Show paths under the root relative to it.
+    pub fn with_root<P>(mut self, root: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.set_root(Some(root.into()));
+        self
+    }
+    /// Set the root paths are shown relative to.
+    pub fn set_root(&mut self, root: Option<PathBuf>) {
+        self.root = root;
+    }
+    /// Get the root paths are shown relative to.
+    pub fn get_root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+    /// Replace the prefix `from` of paths with `to`.
+    pub fn with_prefix<F, T>(mut self, from: F, to: T) -> Self
+    where
+        F: Into<PathBuf>,
+        T: Into<PathBuf>,
+    {
+        self.add_prefix(from, to);
+        self
+    }
+    /// Replace the prefix `from` of paths with `to`, this rule takes precedence over the rules added before.
+    pub fn add_prefix<F, T>(&mut self, from: F, to: T)
+    where
+        F: Into<PathBuf>,
+        T: Into<PathBuf>,
+    {
+        self.prefixes.push((from.into(), to.into()));
+    }
+
+    /// Rewrite a local path, returns `None` if no rule applies to it.
+    pub fn remap(&self, path: &Path) -> Option<PathBuf> {
+        for (from, to) in self.prefixes.iter().rev() {
+            if let Ok(rest) = path.strip_prefix(from) {
+                return Some(match rest.as_os_str().is_empty() {
+                    true => to.clone(),
+                    false => to.join(rest),
+                });
+            }
+        }
+        let rest = path.strip_prefix(self.root.as_ref()?).ok()?;
+        Some(rest.to_path_buf())
+    }
+    /// Show the path as rewritten by the rules, other paths are shown by their [`Display`](std::fmt::Display) implementation.
+    pub fn display(&self, path: &SourcePath) -> String {
+        match path {
+            SourcePath::Local(local) => match self.remap(local) {
+                Some(remapped) => remapped.to_string_lossy().into_owned(),
+                None => path.to_string(),
+            },
+            SourcePath::NotebookCell { notebook, cell } => match self.remap(notebook) {
+                Some(remapped) => format!("{}, cell [{}]", remapped.to_string_lossy(), cell + 1),
+                None => path.to_string(),
+            },
+            SourcePath::Archive { archive, entry } => match self.remap(archive) {
+                Some(remapped) => format!("{}!/{}", remapped.to_string_lossy(), entry),
+                None => path.to_string(),
+            },
+            _ => path.to_string(),
+        }
+    }
+}
@@ -1,13 +1,22 @@
 #![doc = include_str!("../readme.md")]
 #![warn(missing_docs)]
 
+mod archive;
 mod cache;
 mod identifier;
+mod loader;
+mod mapping;
 mod text;
+mod vfs;
 
 pub use crate::{
-    cache::SourceCache,
-    identifier::{SourceID, SourcePath},
-    text::{SourceLine, SourceSpan, SourceText},
+    cache::{CacheCapacity, SharedSourceCache, SourceCache, SourceWatcher},
+    identifier::{PathRemap, SourceID, SourcePath},
+    loader::{DataLoader, FileLoader, SourceLoader, VirtualLoader},
+    mapping::SourceMap,
+    text::{visual_width, LineEnding, SourceLine, SourceSpan, SourceText, TextDecoder, TextEncoding},
+    vfs::{FileLayer, FileSystem, VirtualFileSystem},
 };
+#[cfg(feature = "serde")]
+pub use crate::cache::{SnapshotMode, SourceSnapshot};
 pub use url::Url;
@@ -0,0 +1,60 @@
+use super::*;
+
+impl SourceLoader for DataLoader {
+    fn load(&self, url: &Url) -> Result<String, Error> {
+        let invalid = |msg: &str| Error::new(ErrorKind::InvalidData, format!("{}: {}", msg, url));
+        if url.scheme() != "data" {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("{} is not a data url", url)));
+        }
+        // The payload may contain `?` and `#`, which the url parser splits off as query and fragment
+        let (header, data) = match url.as_str()["data:".len()..].split_once(',') {
+            Some(s) => s,
+            None => return Err(invalid("Missing `,` in data url")),
+        };
+        let mut bytes = percent_decode(data).ok_or_else(|| invalid("Invalid percent encoding in data url"))?;
+        if header.rsplit(';').next().is_some_and(|s| s.eq_ignore_ascii_case("base64")) {
+            bytes = base64_decode(&bytes).ok_or_else(|| invalid("Invalid base64 in data url"))?;
+        }
+        String::from_utf8(bytes).map_err(|_| invalid("Data url is not valid UTF-8"))
+    }
+}
+
+fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut bytes = text.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'%' => {
+                let hi = (bytes.next()? as char).to_digit(16)?;
+                let lo = (bytes.next()? as char).to_digit(16)?;
+                out.push((hi * 16 + lo) as u8);
+            }
+            _ => out.push(byte),
+        }
+    }
+    Some(out)
+}
+
+fn base64_decode(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(bytes.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in bytes.iter().filter(|b| !b.is_ascii_whitespace()) {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        buffer = buffer << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
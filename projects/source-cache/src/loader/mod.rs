@@ -0,0 +1,129 @@
+use crate::{SourceText, Url, VirtualFileSystem};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+    io::{Error, ErrorKind},
+    sync::{Arc, RwLock},
+};
+
+mod data;
+
+/// Fetches the text behind a [`Url`], registered in a [`SourceCache`](crate::SourceCache) for a URL scheme.
+///
+/// Any `Fn(&Url) -> Result<String, std::io::Error>` is a loader, which makes it easy to plug in an HTTP client, or a
+/// local stand-in for one in tests.
+pub trait SourceLoader: Send + Sync {
+    /// Load the text behind the url.
+    fn load(&self, url: &Url) -> Result<String, Error>;
+}
+
+/// Loads `file://` urls from the file system.
+///
+/// [`SourceCache::load_remote`](crate::SourceCache::load_remote) reads local files through its
+/// [`VirtualFileSystem`] instead, so this loader only serves other uses of the registered loaders.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FileLoader;
+
+/// Loads `data:` urls as described in [RFC 2397](https://www.rfc-editor.org/rfc/rfc2397), the content must be UTF-8.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DataLoader;
+
+/// An in-memory registry of texts, addressed by their full url.
+///
+/// Register it with [`SourceCache::add_loader`](crate::SourceCache::add_loader) under a scheme of your choosing, and
+/// keep an [`Arc`] to add files later.
+#[derive(Debug, Default)]
+pub struct VirtualLoader {
+    files: RwLock<HashMap<Url, String>>,
+}
+
+/// The loaders of a [`SourceCache`](crate::SourceCache), by URL scheme.
+#[derive(Clone)]
+pub(crate) struct SourceLoaders {
+    schemes: HashMap<String, Arc<dyn SourceLoader>>,
+}
+
+impl<F> SourceLoader for F
+where
+    F: Fn(&Url) -> Result<String, Error> + Send + Sync,
+{
+    fn load(&self, url: &Url) -> Result<String, Error> {
+        self(url)
+    }
+}
+
+impl<L: SourceLoader + ?Sized> SourceLoader for Arc<L> {
+    fn load(&self, url: &Url) -> Result<String, Error> {
+        L::load(self, url)
+    }
+}
+
+impl SourceLoader for FileLoader {
+    fn load(&self, url: &Url) -> Result<String, Error> {
+        match url.to_file_path() {
+            Ok(path) => std::fs::read_to_string(path),
+            Err(_) => Err(Error::new(ErrorKind::InvalidInput, format!("{} is not a local file", url))),
+        }
+    }
+}
+
+impl VirtualLoader {
+    /// Add or replace the text behind the url, returns the previous text.
+    pub fn insert<S>(&self, url: Url, text: S) -> Option<String>
+    where
+        S: Into<String>,
+    {
+        self.files.write().expect("virtual files are poisoned").insert(url, text.into())
+    }
+    /// Remove the text behind the url, returns the previous text.
+    pub fn remove(&self, url: &Url) -> Option<String> {
+        self.files.write().expect("virtual files are poisoned").remove(url)
+    }
+}
+
+impl SourceLoader for VirtualLoader {
+    fn load(&self, url: &Url) -> Result<String, Error> {
+        match self.files.read().expect("virtual files are poisoned").get(url) {
+            Some(text) => Ok(text.clone()),
+            None => Err(Error::new(ErrorKind::NotFound, format!("{} is not registered", url))),
+        }
+    }
+}
+
+impl SourceLoaders {
+    pub fn insert(&mut self, scheme: &str, loader: Arc<dyn SourceLoader>) -> Option<Arc<dyn SourceLoader>> {
+        self.schemes.insert(scheme.to_ascii_lowercase(), loader)
+    }
+    pub fn remove(&mut self, scheme: &str) -> Option<Arc<dyn SourceLoader>> {
+        self.schemes.remove(&scheme.to_ascii_lowercase())
+    }
+    pub fn load(&self, url: &Url) -> Result<String, Error> {
+        match self.schemes.get(url.scheme()) {
+            Some(loader) => loader.load(url),
+            None => Err(Error::new(ErrorKind::Unsupported, format!("No loader for the `{}` scheme of {}", url.scheme(), url))),
+        }
+    }
+    /// Load the source behind the url, `file://` urls are local files read through the file system, so they see
+    /// unsaved buffers, and other urls remote sources.
+    pub fn load_source(&self, vfs: &VirtualFileSystem, url: Url) -> Result<SourceText, Error> {
+        match url.to_file_path() {
+            Ok(path) if url.scheme() == "file" => vfs.read(path),
+            _ => Ok(SourceText::from(self.load(&url)?).with_remote(url)),
+        }
+    }
+}
+
+impl Default for SourceLoaders {
+    fn default() -> Self {
+        let mut loaders = Self { schemes: HashMap::new() };
+        loaders.insert("file", Arc::new(FileLoader));
+        loaders.insert("data", Arc::new(DataLoader));
+        loaders
+    }
+}
+
+impl Debug for SourceLoaders {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.schemes.keys()).finish()
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{SourceID, SourceSpan};
+
+#[cfg(feature = "notebook")]
+mod notebook;
+#[cfg(feature = "source-map")]
+mod v3;
+
+/// Maps offsets in a generated [`SourceText`](crate::SourceText) back to the sources it was generated from.
+///
+/// The generated text is cut into segments at every mapped offset, each segment maps to the original offset it starts
+/// at, and the offsets inside it map to the original offsets that follow. Register the map with
+/// [`SourceCache::add_source_map`](crate::SourceCache::add_source_map) to resolve spans of the generated text.
+///
+/// ```
+/// # use source_cache::{SourceCache, SourceMap};
+/// let mut cache = SourceCache::default();
+/// let template = cache.load_text("Hello {{ name }}!", "hello.html").unwrap();
+/// let output = cache.load_text("print('Hello ' + name + '!')", "hello.py").unwrap();
+/// let mut map = SourceMap::new(output);
+/// map.add_mapping(7, template, 0);
+/// map.add_mapping(17, template, 9);
+/// map.add_unmapped(21);
+/// let span = map.map_span(output.with_range(17..21)).unwrap();
+/// assert_eq!(span, template.with_range(9..13));
+/// ```
+#[derive(Clone, Debug)]
+pub struct SourceMap {
+    generated: SourceID,
+    /// Segments sorted by their generated offset
+    segments: Vec<MapSegment>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct MapSegment {
+    generated: u32,
+    original: Option<(SourceID, u32)>,
+}
+
+impl SourceMap {
+    /// Create an empty map for the generated source.
+    pub fn new(generated: SourceID) -> Self {
+        Self { generated, segments: vec![] }
+    }
+    /// Get the source the map starts from.
+    pub fn get_generated(&self) -> SourceID {
+        self.generated
+    }
+    /// Get the number of segments in the map.
+    pub fn get_segment_count(&self) -> usize {
+        self.segments.len()
+    }
+    /// Start a segment at the generated offset, which maps to the offset in the original source.
+    pub fn add_mapping(&mut self, generated: u32, file: SourceID, original: u32) {
+        self.add_segment(MapSegment { generated, original: Some((file, original)) });
+    }
+    /// Start a segment at the generated offset that has no original, such as glue code added by the generator.
+    pub fn add_unmapped(&mut self, generated: u32) {
+        self.add_segment(MapSegment { generated, original: None });
+    }
+    fn add_segment(&mut self, segment: MapSegment) {
+        let index = self.segments.partition_point(|s| s.generated <= segment.generated);
+        match index.checked_sub(1) {
+            Some(last) if self.segments[last].generated == segment.generated => self.segments[last] = segment,
+            _ => self.segments.insert(index, segment),
+        }
+    }
+
+    /// Map an offset of the generated source to the original source and offset, if it has one.
+    pub fn map_offset(&self, offset: u32) -> Option<(SourceID, u32)> {
+        let index = self.segments.partition_point(|s| s.generated <= offset).checked_sub(1)?;
+        let segment = self.segments[index];
+        let (file, original) = segment.original?;
+        Some((file, original + (offset - segment.generated)))
+    }
+    /// Map a span of the generated source to the original source.
+    ///
+    /// Returns `None` if the span is in another source or its start has no original. If the end maps to another place
+    /// than where the start leads, the span shrinks to the start.
+    pub fn map_span(&self, span: SourceSpan) -> Option<SourceSpan> {
+        if span.file != self.generated {
+            return None;
+        }
+        let (file, start) = self.map_offset(span.start)?;
+        let end = match span.end.checked_sub(1).filter(|last| *last > span.start) {
+            Some(last) => match self.map_offset(last) {
+                Some((end_file, end)) if end_file == file && end >= start => end + 1,
+                _ => start,
+            },
+            None => start + (span.end - span.start),
+        };
+        Some(SourceSpan { start, end, file })
+    }
+}
@@ -0,0 +1,125 @@
+use super::*;
+use crate::{SourceCache, SourcePath, SourceText};
+use serde_json::value::RawValue;
+use std::{
+    io::{Error, ErrorKind},
+    path::Path,
+    str::CharIndices,
+};
+
+/// The JSON document of a [Jupyter notebook](https://nbformat.readthedocs.io/en/latest/format_description.html), only
+/// the fields used to find the code cells.
+#[derive(serde::Deserialize)]
+struct RawNotebook<'a> {
+    #[serde(borrow)]
+    cells: Vec<RawCell<'a>>,
+}
+
+#[derive(serde::Deserialize)]
+struct RawCell<'a> {
+    cell_type: String,
+    /// A string or a list of strings, kept raw to know where the text is in the file
+    #[serde(borrow)]
+    source: &'a RawValue,
+}
+
+impl SourceCache {
+    /// Load the code cells of a Jupyter notebook as sources of their own, returns them in notebook order.
+    ///
+    /// Each cell is a [`SourcePath::NotebookCell`], so diagnostics in it are shown with a header like
+    /// `analysis.ipynb, cell [3], line 2, column 5`. The notebook file is loaded as well, and a [`SourceMap`] from each
+    /// cell to the JSON text is registered, so [`SourceCache::map_span`] resolves spans in cells to offsets in the file.
+    pub fn load_notebook<P>(&mut self, path: P) -> Result<Vec<SourceID>, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let file = self.load_local(path)?;
+        let json = self.fetch_shared(&file)?;
+        let json = json.text();
+        let notebook: RawNotebook = serde_json::from_str(json).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let mut cells = vec![];
+        for (index, cell) in notebook.cells.iter().enumerate() {
+            if cell.cell_type != "code" {
+                continue;
+            }
+            let cell_path = SourcePath::NotebookCell { notebook: path.to_path_buf(), cell: index as u32 };
+            let mut text = String::new();
+            let mut map = SourceMap::new(cell_path.source_id());
+            let parts: Vec<&RawValue> = match cell.source.get().starts_with('[') {
+                true => serde_json::from_str(cell.source.get()).map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+                false => vec![cell.source],
+            };
+            for part in parts {
+                decode_string(json, part.get(), &mut text, &mut map, file)?;
+            }
+            let mut source = SourceText::from(text);
+            source.set_source(cell_path);
+            cells.push(self.insert(source)?);
+            self.add_source_map(map);
+        }
+        Ok(cells)
+    }
+}
+
+/// Decode a JSON string literal, a slice of `json`, onto `text`, mapping each run of verbatim characters to its
+/// offset in `json`.
+fn decode_string(json: &str, literal: &str, text: &mut String, map: &mut SourceMap, file: SourceID) -> Result<(), Error> {
+    let start = literal.as_ptr() as usize - json.as_ptr() as usize + 1;
+    let content = match literal.strip_prefix('"').and_then(|l| l.strip_suffix('"')) {
+        Some(content) => content,
+        None => return Err(invalid("Cell source is not a string")),
+    };
+    let mut delta = None;
+    let mut chars = content.char_indices();
+    while let Some((index, c)) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next().map(|(_, e)| e) {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some('r') => '\r',
+                Some('b') => '\x08',
+                Some('f') => '\x0C',
+                Some('u') => decode_unicode(&mut chars)?,
+                Some(e @ ('"' | '\\' | '/')) => e,
+                _ => return Err(invalid("Invalid escape in cell source")),
+            },
+            c => c,
+        };
+        let (generated, original) = (text.len() as u32, (start + index) as u32);
+        if delta != Some(original as i64 - generated as i64) {
+            map.add_mapping(generated, file, original);
+            delta = Some(original as i64 - generated as i64);
+        }
+        text.push(c);
+    }
+    Ok(())
+}
+
+/// Decode the hex digits after `\u`, and the low surrogate escape that follows a high surrogate.
+fn decode_unicode(chars: &mut CharIndices) -> Result<char, Error> {
+    let high = hex4(chars)?;
+    if !(0xD800..0xDC00).contains(&high) {
+        return Ok(char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER));
+    }
+    let mut rest = chars.clone();
+    if rest.next().map(|(_, c)| c) != Some('\\') || rest.next().map(|(_, c)| c) != Some('u') {
+        return Ok(char::REPLACEMENT_CHARACTER);
+    }
+    match hex4(&mut rest)? {
+        low @ 0xDC00..0xE000 => {
+            *chars = rest;
+            Ok(char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).unwrap_or(char::REPLACEMENT_CHARACTER))
+        }
+        _ => Ok(char::REPLACEMENT_CHARACTER),
+    }
+}
+
+fn hex4(chars: &mut CharIndices) -> Result<u32, Error> {
+    let digits: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+    u32::from_str_radix(&digits, 16).map_err(|_| invalid("Invalid unicode escape in cell source"))
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
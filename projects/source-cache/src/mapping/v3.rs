@@ -0,0 +1,154 @@
+use super::*;
+use crate::{SourceCache, SourcePath, SourceText, Url};
+use std::{
+    io::{Error, ErrorKind},
+    path::PathBuf,
+};
+
+/// The JSON document of a [Source Map v3](https://tc39.es/source-map/), only the fields used for mapping.
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSourceMap {
+    version: u32,
+    #[serde(default)]
+    source_root: Option<String>,
+    #[serde(default)]
+    sources: Vec<Option<String>>,
+    #[serde(default)]
+    sources_content: Vec<Option<String>>,
+    #[serde(default)]
+    mappings: String,
+    #[serde(default)]
+    sections: Option<serde::de::IgnoredAny>,
+}
+
+impl SourceCache {
+    /// Parse a [Source Map v3](https://tc39.es/source-map/) document for the generated source, load its original
+    /// sources and register it, see [`SourceCache::add_source_map`].
+    ///
+    /// Sources listed with their content in `sourcesContent` are taken from the map, the others are loaded as local
+    /// files relative to the generated source, or as remote urls. Columns are counted in UTF-16 code units as the format
+    /// requires. Index maps with `sections` are not supported.
+    pub fn load_source_map(&mut self, generated: SourceID, json: &str) -> Result<(), Error> {
+        let raw: RawSourceMap = serde_json::from_str(json).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        if raw.version != 3 {
+            return Err(Error::new(ErrorKind::Unsupported, format!("Source map version {} is not supported", raw.version)));
+        }
+        if raw.sections.is_some() {
+            return Err(Error::new(ErrorKind::Unsupported, "Indexed source maps are not supported"));
+        }
+        let base = match self.source_path(&generated) {
+            Some(SourcePath::Local(path)) => path.parent().map(|p| p.to_path_buf()).unwrap_or_default(),
+            Some(_) => PathBuf::new(),
+            None => return Err(Error::new(ErrorKind::NotFound, format!("File {:?} not found", generated))),
+        };
+        let root = raw.source_root.unwrap_or_default();
+        let mut files = Vec::with_capacity(raw.sources.len());
+        for (index, source) in raw.sources.iter().enumerate() {
+            let source = match source {
+                Some(s) => format!("{}{}", root, s),
+                None => {
+                    files.push(None);
+                    continue;
+                }
+            };
+            // Single letters are windows drive letters rather than url schemes
+            let path = match Url::parse(&source) {
+                Ok(url) if url.scheme().len() > 1 => SourcePath::Remote(url),
+                _ => SourcePath::Local(base.join(&source)),
+            };
+            let file = match raw.sources_content.get(index).cloned().flatten() {
+                Some(text) => {
+                    let mut text = SourceText::from(text);
+                    text.set_source(path);
+                    self.insert(text)?
+                }
+                None => match path {
+                    SourcePath::Remote(url) => self.load_remote(url)?,
+                    _ => self.load_local(base.join(&source))?,
+                },
+            };
+            files.push(Some(file));
+        }
+        let map = self.parse_mappings(generated, &files, &raw.mappings)?;
+        self.add_source_map(map);
+        Ok(())
+    }
+
+    fn parse_mappings(&self, generated: SourceID, files: &[Option<SourceID>], mappings: &str) -> Result<SourceMap, Error> {
+        let output = self.fetch(&generated)?;
+        let mut map = SourceMap::new(generated);
+        // Every field but the generated column is relative to the previous segment of the whole map
+        let (mut source, mut original_line, mut original_column) = (0i64, 0i64, 0i64);
+        for (line, segments) in mappings.split(';').enumerate() {
+            let mut column = 0i64;
+            for segment in segments.split(',').filter(|s| !s.is_empty()) {
+                let fields = decode_vlq(segment)?;
+                column += fields[0];
+                let offset = match utf16_offset(output, line, column) {
+                    Some(s) => s,
+                    None => continue,
+                };
+                if fields.len() < 4 {
+                    map.add_unmapped(offset);
+                    continue;
+                }
+                source += fields[1];
+                original_line += fields[2];
+                original_column += fields[3];
+                let original = files.get(source as usize).copied().flatten().and_then(|file| {
+                    let text = self.fetch(&file).ok()?;
+                    Some((file, utf16_offset(text, original_line as usize, original_column)?))
+                });
+                match original {
+                    Some((file, original)) => map.add_mapping(offset, file, original),
+                    None => map.add_unmapped(offset),
+                }
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// Decode a segment of base64 VLQ numbers.
+fn decode_vlq(segment: &str) -> Result<Vec<i64>, Error> {
+    let mut fields = Vec::with_capacity(5);
+    let (mut value, mut shift) = (0i64, 0);
+    for byte in segment.bytes() {
+        let digit = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(Error::new(ErrorKind::InvalidData, format!("Invalid mapping segment `{}`", segment))),
+        } as i64;
+        if shift > 60 {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Mapping segment `{}` overflows", segment)));
+        }
+        value |= (digit & 0b11111) << shift;
+        shift += 5;
+        if digit & 0b100000 == 0 {
+            // The lowest bit is the sign
+            fields.push(if value & 1 == 1 { -(value >> 1) } else { value >> 1 });
+            (value, shift) = (0, 0);
+        }
+    }
+    if shift != 0 || !matches!(fields.len(), 1 | 4 | 5) {
+        return Err(Error::new(ErrorKind::InvalidData, format!("Invalid mapping segment `{}`", segment)));
+    }
+    Ok(fields)
+}
+
+/// Find the byte offset of a zero-based line and UTF-16 column, columns past the end of the line stop at its end.
+fn utf16_offset(text: &SourceText, line: usize, column: i64) -> Option<u32> {
+    let line = text.get_line(line)?;
+    let mut units = 0;
+    for (index, char) in line.view(text).char_indices() {
+        if units >= column {
+            return Some(line.text.start + index as u32);
+        }
+        units += char.len_utf16() as i64;
+    }
+    Some(line.text.end)
+}
@@ -0,0 +1,62 @@
+use memmap2::Mmap;
+use std::{
+    fmt::{Debug, Formatter},
+    fs::File,
+    ops::Deref,
+    sync::Arc,
+};
+
+/// The storage behind a [`SourceText`](super::SourceText), always valid UTF-8.
+#[derive(Clone)]
+pub(crate) enum SourceBuffer {
+    /// Text owned on the heap
+    Owned(String),
+    /// A read-only memory map of a local file, validated as UTF-8 when created
+    Mapped(Arc<Mmap>),
+}
+
+impl SourceBuffer {
+    /// Map the file into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the map is alive, see [`Mmap::map`].
+    pub unsafe fn map(file: &File) -> std::io::Result<Self> {
+        // Mapping an empty file fails on some platforms
+        if file.metadata()?.len() == 0 {
+            return Ok(Self::Owned(String::new()));
+        }
+        let map = Mmap::map(file)?;
+        if let Err(e) = std::str::from_utf8(&map) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+        }
+        Ok(Self::Mapped(Arc::new(map)))
+    }
+}
+
+impl Default for SourceBuffer {
+    fn default() -> Self {
+        Self::Owned(String::new())
+    }
+}
+
+impl Deref for SourceBuffer {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Owned(s) => s.as_str(),
+            // SAFETY: checked in `SourceBuffer::map`
+            Self::Mapped(m) => unsafe { std::str::from_utf8_unchecked(m) },
+        }
+    }
+}
+
+impl Debug for SourceBuffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Owned(s) => Debug::fmt(s, f),
+            Self::Mapped(m) => write!(f, "Mapped({} bytes)", m.len()),
+        }
+    }
+}
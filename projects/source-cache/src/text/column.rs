@@ -0,0 +1,70 @@
+use super::*;
+use unicode_width::UnicodeWidthChar;
+
+/// Get the number of columns a character takes up when shown starting at the zero-based visual `column`.
+///
+/// Tabs run to the next multiple of `tab_width`, other whitespace and control characters take one column, and the rest
+/// take their unicode width, so wide CJK characters take two columns and combining marks none.
+///
+/// ```
+/// # use source_cache::visual_width;
+/// assert_eq!(visual_width('\t', 1, 4), 3);
+/// assert_eq!(visual_width('中', 0, 4), 2);
+/// assert_eq!(visual_width('\u{301}', 0, 4), 0);
+/// ```
+pub fn visual_width(c: char, column: usize, tab_width: usize) -> usize {
+    match c {
+        '\t' => match tab_width {
+            0 => 0,
+            w => (column / w + 1) * w - column,
+        },
+        c if c.is_whitespace() => 1,
+        c => c.width().unwrap_or(1),
+    }
+}
+
+impl SourceLine {
+    /// Get the zero-based visual column of a byte column in the line, as counted by [`visual_width`].
+    ///
+    /// Columns inside a character count from its start, columns past the visible text count every byte as one column.
+    pub fn get_visual_column(&self, source: &SourceText, column: u32, tab_width: usize) -> usize {
+        let mut visual = 0;
+        for (index, c) in self.view(source).char_indices() {
+            if index as u32 + c.len_utf8() as u32 > column {
+                return visual;
+            }
+            visual += visual_width(c, visual, tab_width);
+        }
+        visual + column.saturating_sub(self.text.end - self.text.start) as usize
+    }
+    /// Get the byte column in the line of the character shown at a zero-based visual column, the inverse of
+    /// [`SourceLine::get_visual_column`].
+    ///
+    /// Visual columns inside a wide character or a tab map to the start of that character.
+    pub fn get_byte_column(&self, source: &SourceText, visual: usize, tab_width: usize) -> u32 {
+        let mut start = 0;
+        for (index, c) in self.view(source).char_indices() {
+            let end = start + visual_width(c, start, tab_width);
+            if visual < end {
+                return index as u32;
+            }
+            start = end;
+        }
+        let column = self.text.end - self.text.start + (visual - start) as u32;
+        column.min(self.length)
+    }
+}
+
+impl SourceText {
+    /// Get the zero-based line and visual column of the offset, see [`SourceLine::get_visual_column`].
+    pub fn get_visual_position(&self, offset: u32, tab_width: usize) -> Option<(usize, usize)> {
+        let (line, index, column) = self.get_offset_line(offset)?;
+        Some((index, line.get_visual_column(self, column, tab_width)))
+    }
+    /// Get the offset of the character shown at a zero-based line and visual column, see
+    /// [`SourceLine::get_byte_column`].
+    pub fn get_visual_offset(&self, line: usize, visual: usize, tab_width: usize) -> Option<u32> {
+        let line = self.get_line(line)?;
+        Some(line.offset + line.get_byte_column(self, visual, tab_width))
+    }
+}
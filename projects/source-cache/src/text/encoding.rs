@@ -0,0 +1,250 @@
+use super::*;
+use std::io::{Error, ErrorKind};
+
+/// The encoding of a file as stored, [`SourceText`]s are always decoded to UTF-8.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TextEncoding {
+    /// UTF-8, with or without a byte order mark
+    #[default]
+    Utf8,
+    /// UTF-16 little endian
+    Utf16Le,
+    /// UTF-16 big endian
+    Utf16Be,
+    /// ISO-8859-1, every byte is the code point of the same value
+    Latin1,
+}
+
+/// Decodes the bytes of a file into a [`SourceText`].
+///
+/// By default the encoding is detected from the byte order mark, falling back to UTF-8, and invalid input is an error.
+/// The decoded text remembers where each character was in the file, see [`SourceText::get_text_offset`].
+///
+/// ```
+/// # use source_cache::{TextDecoder, TextEncoding};
+/// let text = TextDecoder::default().decode(vec![0xFF, 0xFE, b'h', 0, b'i', 0]).unwrap();
+/// assert_eq!(text.text(), "hi");
+/// assert_eq!(text.get_encoding(), TextEncoding::Utf16Le);
+/// assert_eq!(text.get_text_offset(4), 1);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextDecoder {
+    encoding: Option<TextEncoding>,
+    lossy: bool,
+}
+
+/// How the text of a [`SourceText`] was decoded from its file, absent for plain UTF-8.
+#[derive(Clone, Debug)]
+pub(crate) struct Decoding {
+    pub encoding: TextEncoding,
+    pub offsets: OffsetMap,
+    /// The text offsets of the replacement characters inserted for invalid input
+    pub replacements: Box<[u32]>,
+}
+
+/// Maps between byte offsets in a file and in the text decoded from it.
+///
+/// Consecutive characters with the same width in both are stored as one run, so mostly ASCII files need few runs.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OffsetMap {
+    runs: Vec<OffsetRun>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct OffsetRun {
+    file: u32,
+    text: u32,
+    file_width: u32,
+    text_width: u32,
+    count: u32,
+}
+
+impl TextDecoder {
+    /// Decode with the given encoding instead of detecting it, a matching byte order mark is still skipped.
+    pub fn with_encoding(mut self, encoding: TextEncoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+    /// Replace invalid input with U+FFFD instead of failing, see [`SourceText::get_replacements`].
+    pub fn with_lossy(mut self, lossy: bool) -> Self {
+        self.lossy = lossy;
+        self
+    }
+    /// Decode the bytes into an anonymous [`SourceText`].
+    ///
+    /// Fails with [`ErrorKind::InvalidData`] on invalid input, unless the decoder is lossy.
+    pub fn decode(&self, bytes: Vec<u8>) -> Result<SourceText, Error> {
+        let (encoding, bom) = match (self.encoding, bytes.as_slice()) {
+            (None | Some(TextEncoding::Utf8), [0xEF, 0xBB, 0xBF, ..]) => (TextEncoding::Utf8, 3),
+            (None | Some(TextEncoding::Utf16Le), [0xFF, 0xFE, ..]) => (TextEncoding::Utf16Le, 2),
+            (None | Some(TextEncoding::Utf16Be), [0xFE, 0xFF, ..]) => (TextEncoding::Utf16Be, 2),
+            (None, _) => (TextEncoding::Utf8, 0),
+            (Some(encoding), _) => (encoding, 0),
+        };
+        if encoding == TextEncoding::Utf8 && bom == 0 {
+            // The common case keeps the buffer and needs no offset map
+            match String::from_utf8(bytes) {
+                Ok(text) => return Ok(SourceText::from(text)),
+                Err(e) if !self.lossy => return Err(Error::new(ErrorKind::InvalidData, e.utf8_error())),
+                Err(e) => return self.decode_with(encoding, &e.into_bytes(), 0),
+            }
+        }
+        self.decode_with(encoding, &bytes, bom)
+    }
+    /// Check whether decoding is sure to fail on bytes that start with the prefix, to skip binary files early.
+    #[cfg(any(feature = "zip", feature = "tar"))]
+    pub(crate) fn rejects_prefix(&self, prefix: &[u8]) -> bool {
+        // A sequence cut off at the end of the prefix may still be completed
+        let invalid_utf8 = |bytes: &[u8]| std::str::from_utf8(bytes).is_err_and(|e| e.error_len().is_some());
+        match (self.encoding, prefix) {
+            _ if self.lossy => false,
+            (None | Some(TextEncoding::Utf8), [0xEF, 0xBB, 0xBF, rest @ ..]) => invalid_utf8(rest),
+            (None, [0xFF, 0xFE, ..] | [0xFE, 0xFF, ..]) => false,
+            (None | Some(TextEncoding::Utf8), _) => invalid_utf8(prefix),
+            _ => false,
+        }
+    }
+    fn decode_with(&self, encoding: TextEncoding, bytes: &[u8], bom: u32) -> Result<SourceText, Error> {
+        let mut decoder =
+            Decoder { text: String::with_capacity(bytes.len()), offsets: OffsetMap::default(), replacements: vec![] };
+        if bom > 0 {
+            // The byte order mark is the only run without text
+            decoder.offsets.runs.push(OffsetRun { file: 0, text: 0, file_width: bom, text_width: 0, count: 1 });
+        }
+        let bytes = &bytes[bom as usize..];
+        match encoding {
+            TextEncoding::Utf8 => {
+                for chunk in bytes.utf8_chunks() {
+                    chunk.valid().chars().for_each(|c| decoder.push(c, c.len_utf8() as u32));
+                    if !chunk.invalid().is_empty() {
+                        self.invalid(&mut decoder, chunk.invalid().len() as u32)?;
+                    }
+                }
+            }
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+                let units = bytes.chunks_exact(2).map(|pair| match encoding {
+                    TextEncoding::Utf16Le => u16::from_le_bytes([pair[0], pair[1]]),
+                    _ => u16::from_be_bytes([pair[0], pair[1]]),
+                });
+                for c in char::decode_utf16(units) {
+                    match c {
+                        Ok(c) => decoder.push(c, c.len_utf16() as u32 * 2),
+                        Err(_) => self.invalid(&mut decoder, 2)?,
+                    }
+                }
+                if bytes.len() % 2 == 1 {
+                    self.invalid(&mut decoder, 1)?;
+                }
+            }
+            TextEncoding::Latin1 => bytes.iter().for_each(|b| decoder.push(*b as char, 1)),
+        }
+        let Decoder { text, offsets, replacements } = decoder;
+        let mut source = SourceText::from(text);
+        source.decoding = Some(Arc::new(Decoding { encoding, offsets, replacements: replacements.into_boxed_slice() }));
+        Ok(source)
+    }
+    fn invalid(&self, decoder: &mut Decoder, file_width: u32) -> Result<(), Error> {
+        if !self.lossy {
+            return Err(Error::new(ErrorKind::InvalidData, format!("Invalid input at byte {}", decoder.offsets.file_length())));
+        }
+        decoder.replacements.push(decoder.text.len() as u32);
+        decoder.push(char::REPLACEMENT_CHARACTER, file_width);
+        Ok(())
+    }
+}
+
+struct Decoder {
+    text: String,
+    offsets: OffsetMap,
+    replacements: Vec<u32>,
+}
+
+impl Decoder {
+    fn push(&mut self, c: char, file_width: u32) {
+        self.text.push(c);
+        let text_width = c.len_utf8() as u32;
+        match self.offsets.runs.last_mut() {
+            Some(last) if last.file_width == file_width && last.text_width == text_width => last.count += 1,
+            last => {
+                let (file, text) = last.map_or((0, 0), |r| (r.file + r.file_width * r.count, r.text + r.text_width * r.count));
+                self.offsets.runs.push(OffsetRun { file, text, file_width, text_width, count: 1 });
+            }
+        }
+    }
+}
+
+impl OffsetMap {
+    fn file_length(&self) -> u32 {
+        self.runs.last().map_or(0, |r| r.file + r.file_width * r.count)
+    }
+    /// Map a file offset to the start of the character it falls in.
+    pub fn to_text(&self, offset: u32) -> u32 {
+        let index = self.runs.partition_point(|r| r.file <= offset).saturating_sub(1);
+        match self.runs.get(index) {
+            Some(run) => run.text + run.text_width * ((offset - run.file.min(offset)) / run.file_width).min(run.count),
+            None => offset,
+        }
+    }
+    /// Map a text offset to the start of the character it falls in.
+    pub fn to_file(&self, offset: u32) -> u32 {
+        let (bom, runs) = match self.runs.split_first() {
+            Some((first, rest)) if first.text_width == 0 => (first.file_width, rest),
+            _ => (0, self.runs.as_slice()),
+        };
+        let index = runs.partition_point(|r| r.text <= offset).saturating_sub(1);
+        match runs.get(index) {
+            Some(run) => run.file + run.file_width * ((offset - run.text.min(offset)) / run.text_width).min(run.count),
+            None => bom + offset,
+        }
+    }
+}
+
+impl SourceText {
+    /// Read a local file with the given decoder, see [`SourceText::read`].
+    pub fn read_with(path: &Path, decoder: &TextDecoder) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let modified = file.metadata()?.modified().ok();
+        let mut bytes = vec![];
+        std::io::Read::read_to_end(&mut file, &mut bytes)?;
+        let source = decoder.decode(bytes)?;
+        Ok(Self { modified, ..source.with_path(path) })
+    }
+    /// Get the encoding of the file the text was decoded from.
+    pub fn get_encoding(&self) -> TextEncoding {
+        self.decoding.as_ref().map_or(TextEncoding::Utf8, |d| d.encoding)
+    }
+    /// Get the offsets of the replacement characters inserted for invalid input by a lossy [`TextDecoder`].
+    pub fn get_replacements(&self) -> &[u32] {
+        self.decoding.as_ref().map_or(&[], |d| &d.replacements)
+    }
+    /// Map a byte offset in the file the text was decoded from to an offset in the text.
+    ///
+    /// Offsets inside a character map to the start of the character, for UTF-8 files without a byte order mark the
+    /// offsets are the same.
+    pub fn get_text_offset(&self, file_offset: u32) -> u32 {
+        match &self.decoding {
+            Some(decoding) => decoding.offsets.to_text(file_offset),
+            None => file_offset,
+        }
+    }
+    /// Map an offset in the text to a byte offset in the file the text was decoded from.
+    pub fn get_file_offset(&self, text_offset: u32) -> u32 {
+        match &self.decoding {
+            Some(decoding) => decoding.offsets.to_file(text_offset),
+            None => text_offset,
+        }
+    }
+    /// Map a span of byte offsets in the file the text was decoded from to a span of the text.
+    pub fn get_text_span(&self, file_span: SourceSpan) -> SourceSpan {
+        let start = self.get_text_offset(file_span.start);
+        let end = match file_span.end > file_span.start {
+            // Round the end up to the end of the character it falls in
+            true => {
+                let last = self.get_text_offset(file_span.end - 1);
+                last + self.text()[last as usize..].chars().next().map_or(0, |c| c.len_utf8() as u32)
+            }
+            false => start,
+        };
+        SourceSpan { start, end, file: file_span.file }
+    }
+}
@@ -0,0 +1,120 @@
+use super::*;
+
+/// The terminator a [`SourceLine`] ends with.
+///
+/// ```
+/// # use source_cache::{LineEnding, SourceText};
+/// let text = SourceText::from("a\r\nb\nc\r\n");
+/// assert_eq!(text.get_line_ending(), Some(LineEnding::CrLf));
+/// let mixed = text.get_mixed_endings();
+/// assert_eq!(mixed.iter().map(|s| s.get_range()).collect::<Vec<_>>(), vec![4..5]);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineEnding {
+    /// The last line of a text that does not end with a terminator
+    #[default]
+    None,
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// `\r`
+    Cr,
+    /// U+000B Vertical tab
+    VerticalTab,
+    /// U+000C Form feed
+    FormFeed,
+    /// U+0085 Next line
+    NextLine,
+    /// U+2028 Line separator
+    LineSeparator,
+    /// U+2029 Paragraph separator
+    ParagraphSeparator,
+}
+
+impl LineEnding {
+    /// Get the terminator that the text starts with.
+    pub fn from_prefix(text: &str) -> Self {
+        match text.as_bytes() {
+            [b'\r', b'\n', ..] => Self::CrLf,
+            [b'\r', ..] => Self::Cr,
+            [b'\n', ..] => Self::Lf,
+            [b'\x0B', ..] => Self::VerticalTab,
+            [b'\x0C', ..] => Self::FormFeed,
+            [0xC2, 0x85, ..] => Self::NextLine,
+            [0xE2, 0x80, 0xA8, ..] => Self::LineSeparator,
+            [0xE2, 0x80, 0xA9, ..] => Self::ParagraphSeparator,
+            _ => Self::None,
+        }
+    }
+    /// Get the text of the terminator, to write new lines the way a file already does.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+            Self::Cr => "\r",
+            Self::VerticalTab => "\x0B",
+            Self::FormFeed => "\x0C",
+            Self::NextLine => "\u{85}",
+            Self::LineSeparator => "\u{2028}",
+            Self::ParagraphSeparator => "\u{2029}",
+        }
+    }
+    /// Get the number of bytes the terminator occupies.
+    pub fn len(&self) -> u32 {
+        self.as_str().len() as u32
+    }
+    /// Check whether this is [`LineEnding::None`].
+    pub fn is_empty(&self) -> bool {
+        *self == Self::None
+    }
+    /// Check whether this is a newline, LF, CRLF or CR, rather than a page break or a Unicode separator.
+    pub fn is_newline(&self) -> bool {
+        matches!(self, Self::Lf | Self::CrLf | Self::Cr)
+    }
+}
+
+impl SourceLine {
+    /// Get the range of the line terminator in the original [`SourceText`], empty for the last line if it has none.
+    pub fn ending_range(&self) -> Range<u32> {
+        let end = self.offset + self.length;
+        end - self.ending.len()..end
+    }
+}
+
+impl SourceText {
+    /// Get the newline most lines end with, ties go to the one used first.
+    ///
+    /// Only newlines count, see [`LineEnding::is_newline`], so form feeds between pages of a C file or Unicode line
+    /// separators inside a paragraph do not decide the line ending. Returns `None` if the text has no newlines.
+    pub fn get_line_ending(&self) -> Option<LineEnding> {
+        let mut counts: Vec<(LineEnding, usize)> = vec![];
+        for line in self.lines().filter(|l| l.ending.is_newline()) {
+            match counts.iter_mut().find(|(ending, _)| *ending == line.ending) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((line.ending, 1)),
+            }
+        }
+        // `max_by_key` keeps the last maximum, iterate in reverse so the first one wins
+        counts.into_iter().rev().max_by_key(|(_, count)| *count).map(|(ending, _)| ending)
+    }
+    /// Get the spans of the newlines that differ from [`SourceText::get_line_ending`].
+    ///
+    /// Empty if all lines end with the same newline, other terminators are never reported.
+    pub fn get_mixed_endings(&self) -> Vec<SourceSpan> {
+        let Some(ending) = self.get_line_ending()
+        else {
+            return vec![];
+        };
+        let file = self.source_id();
+        self.lines()
+            .filter(|l| l.ending.is_newline() && l.ending != ending)
+            .map(|l| {
+                let range = l.ending_range();
+                SourceSpan { start: range.start, end: range.end, file }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,217 @@
+use super::{LineEnding, SourceLine};
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+/// The number of bytes indexed at once by a lazy [`LineIndex`].
+pub(crate) const LAZY_BLOCK_SIZE: usize = 64 * 1024;
+
+/// The line table of a [`SourceText`](super::SourceText).
+///
+/// The text is cut into blocks of `block_size` bytes, each block holds the lines starting in it. Blocks are indexed on
+/// first use and always in order, so a lazy index only ever covers the text up to the furthest offset queried.
+#[derive(Debug)]
+pub(crate) struct LineIndex {
+    block_size: usize,
+    blocks: Box<[OnceLock<LineBlock>]>,
+    /// The number of leading blocks that have been indexed
+    ready: AtomicUsize,
+}
+
+#[derive(Clone, Debug)]
+struct LineBlock {
+    /// The index of the first line in this block
+    first: usize,
+    lines: Box<[SourceLine]>,
+}
+
+impl LineIndex {
+    /// Index the whole text at once.
+    pub fn eager(text: &str) -> Self {
+        let index = Self::lazy(text.len(), text.len().max(1));
+        index.count(text);
+        index
+    }
+    /// Use a line table computed earlier, returns `None` if it does not fit the text, see [`fits_text`].
+    pub fn restored(text: &str, lines: Box<[SourceLine]>) -> Option<Self> {
+        if !fits_text(text, &lines) {
+            return None;
+        }
+        let index = Self::lazy(text.len(), text.len().max(1));
+        if let Some(block) = index.blocks.first() {
+            block.set(LineBlock { first: 0, lines }).ok()?;
+            index.ready.store(1, Ordering::Release);
+        }
+        Some(index)
+    }
+    /// Index nothing until asked for, in blocks of `block_size` bytes.
+    pub fn lazy(length: usize, block_size: usize) -> Self {
+        let blocks = (0..length.div_ceil(block_size)).map(|_| OnceLock::new()).collect();
+        Self { block_size, blocks, ready: AtomicUsize::new(0) }
+    }
+    /// The number of bytes covered by the indexed blocks.
+    pub fn indexed(&self, text: &str) -> usize {
+        (self.ready.load(Ordering::Acquire) * self.block_size).min(text.len())
+    }
+    /// Get the `line`-th line, indexing the text up to it.
+    pub fn get(&self, text: &str, line: usize) -> Option<&SourceLine> {
+        let ready = self.ready.load(Ordering::Acquire);
+        let mut block = self.blocks[..ready].partition_point(|b| b.get().is_some_and(|b| b.first <= line)).saturating_sub(1);
+        while block < self.blocks.len() {
+            let LineBlock { first, lines } = self.block(text, block);
+            if line < first + lines.len() {
+                return lines.get(line - first);
+            }
+            block += 1;
+        }
+        None
+    }
+    /// Find the line containing `offset`, indexing the text up to it.
+    pub fn find(&self, text: &str, offset: u32) -> Option<(usize, &SourceLine)> {
+        let mut block = (offset as usize / self.block_size).min(self.blocks.len().checked_sub(1)?);
+        loop {
+            let LineBlock { first, lines } = self.block(text, block);
+            // A block is empty when a single line runs across all of it
+            match lines.partition_point(|line| line.offset <= offset) {
+                0 if block == 0 => return None,
+                0 => block -= 1,
+                n => return Some((first + n - 1, &lines[n - 1])),
+            }
+        }
+    }
+    /// Iterate over all lines, indexing the whole text.
+    pub fn iter<'a>(&'a self, text: &'a str) -> impl Iterator<Item = &'a SourceLine> + 'a {
+        (0..self.blocks.len()).flat_map(move |block| self.block(text, block).lines.iter())
+    }
+    /// Count all lines, indexing the whole text.
+    pub fn count(&self, text: &str) -> usize {
+        match self.blocks.len().checked_sub(1) {
+            Some(last) => {
+                let block = self.block(text, last);
+                block.first + block.lines.len()
+            }
+            None => 0,
+        }
+    }
+    fn block(&self, text: &str, block: usize) -> &LineBlock {
+        let mut ready = self.ready.load(Ordering::Acquire);
+        while ready <= block {
+            let first = match ready.checked_sub(1) {
+                Some(last) => self.blocks[last].get().map_or(0, |b| b.first + b.lines.len()),
+                None => 0,
+            };
+            let start = ready * self.block_size;
+            self.blocks[ready].get_or_init(|| LineBlock { first, lines: index_lines(text, start..start + self.block_size) });
+            ready += 1;
+            self.ready.fetch_max(ready, Ordering::AcqRel);
+        }
+        self.blocks[block].get().expect("block is indexed")
+    }
+}
+
+impl Clone for LineIndex {
+    fn clone(&self) -> Self {
+        Self {
+            block_size: self.block_size,
+            blocks: self.blocks.clone(),
+            ready: AtomicUsize::new(self.ready.load(Ordering::Acquire)),
+        }
+    }
+}
+
+/// Find the first line terminator at or after `index`, returns the bytes it occupies.
+///
+/// Recognizes CR, LF, CRLF, VT, FF, NEL, LS and PS. The scan works on bytes since every terminator starts with a byte
+/// that can not appear inside another UTF-8 sequence.
+fn next_terminator(bytes: &[u8], mut index: usize) -> Option<Range<usize>> {
+    while index < bytes.len() {
+        let length = match bytes[index] {
+            b'\n' | b'\x0B' | b'\x0C' => 1,
+            b'\r' if bytes.get(index + 1) == Some(&b'\n') => 2,
+            b'\r' => 1,
+            // U+0085 Next line
+            0xC2 if bytes.get(index + 1) == Some(&0x85) => 2,
+            // U+2028 Line separator, U+2029 Paragraph separator
+            0xE2 if bytes.get(index + 1) == Some(&0x80) && matches!(bytes.get(index + 2), Some(0xA8 | 0xA9)) => 3,
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+        return Some(index..index + length);
+    }
+    None
+}
+
+/// Check the invariants of a line table that hold without splitting the text again: the lines tile the text, start
+/// and end on character boundaries and end with the terminator that is there.
+///
+/// Only the terminators the table names are looked at, so this takes time in the number of lines rather than the
+/// length of the text. A table that misses a terminator inside a line still passes, which is why it is only trusted
+/// for a text whose hash was checked.
+fn fits_text(text: &str, lines: &[SourceLine]) -> bool {
+    let mut offset = 0;
+    for (index, line) in lines.iter().enumerate() {
+        let (start, length) = (line.offset as usize, line.length as usize);
+        let Some(body) = length.checked_sub(line.ending.len() as usize).filter(|_| start == offset && length > 0)
+        else {
+            return false;
+        };
+        let (visible, end) = (start + body, start + length);
+        let fits = text.get(start..end).is_some()
+            && text.get(visible..end) == Some(line.ending.as_str())
+            // A `\r\n` is never cut into a `\r` and a `\n` line ending
+            && !(line.ending == LineEnding::Lf && text[..visible].ends_with('\r'))
+            && (!line.ending.is_empty() || index + 1 == lines.len())
+            && line.text.start as usize == start
+            && line.text.end as usize <= visible
+            && text.is_char_boundary(line.text.end as usize);
+        if !fits {
+            return false;
+        }
+        offset = end;
+    }
+    offset == text.len()
+}
+
+/// Split the lines starting in `range` out of `text` without copying any of the text.
+///
+/// The last line may run past the end of `range`.
+fn index_lines(text: &str, range: Range<usize>) -> Box<[SourceLine]> {
+    let bytes = text.as_bytes();
+    let end = range.end.min(bytes.len());
+    // The longest terminator is 3 bytes, start early enough to see one that ends at `range.start`
+    let mut start = match range.start {
+        0 => 0,
+        s => {
+            let mut index = s - 3.min(s);
+            loop {
+                match next_terminator(bytes, index) {
+                    Some(t) if t.end >= s => break t.end,
+                    Some(t) => index = t.end,
+                    None => break bytes.len(),
+                }
+            }
+        }
+    };
+    let mut lines = Vec::with_capacity((end - range.start.min(end)) / 32 + 1);
+    while start < end {
+        let (next, ending) = match next_terminator(bytes, start) {
+            Some(t) => (t.end, LineEnding::from_prefix(&text[t.start..])),
+            None => (bytes.len(), LineEnding::None),
+        };
+        let visible = text[start..next].trim_end().len();
+        lines.push(SourceLine {
+            offset: start as u32,
+            length: (next - start) as u32,
+            text: start as u32..(start + visible) as u32,
+            ending,
+        });
+        start = next;
+    }
+    lines.into_boxed_slice()
+}
@@ -1,40 +1,73 @@
-use crate::{SourceID, SourcePath};
+use crate::{identifier::StableHasher, SourceID, SourcePath};
 use std::{
     borrow::Cow,
     fmt::{Debug, Display, Formatter},
+    fs::File,
+    hash::{Hash, Hasher},
     ops::Range,
     path::Path,
+    sync::{Arc, OnceLock},
+    time::SystemTime,
 };
 use url::Url;
 
+mod buffer;
+mod column;
 mod display;
+mod encoding;
+mod ending;
+mod index;
+mod span;
+
+pub use self::{
+    column::visual_width,
+    encoding::{TextDecoder, TextEncoding},
+    ending::LineEnding,
+};
+use self::{
+    buffer::SourceBuffer,
+    encoding::Decoding,
+    index::{LineIndex, LAZY_BLOCK_SIZE},
+};
 
 /// A type representing a single identifier that may be referred to by [`Span`]s.
 ///
 /// In most cases, an identifier is a single input file.
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug)]
 pub struct SourceText {
     /// The path of the identifier.
     path: SourcePath,
     /// The text
-    raw: String,
+    raw: SourceBuffer,
     /// The lines of the identifier.
-    lines: Vec<SourceLine>,
+    lines: LineIndex,
     /// bytes in identifier
     length: u32,
     /// Is the data dirty
     dirty: bool,
+    /// The modification time of the file when it was read
+    modified: Option<SystemTime>,
+    /// The hash of the text, computed on first use
+    hash: OnceLock<u64>,
+    /// How the text was decoded from its file, if it was not plain UTF-8
+    decoding: Option<Arc<Decoding>>,
 }
 
 /// A type representing a single line of a [`SourceText`].
+///
+/// The line does not own its text, use [`SourceLine::view`] to borrow it from the [`SourceText`] it belongs to.
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SourceLine {
     /// Get the offset of this line in the original [`SourceText`] (i.e: the number of characters that precede it).
     pub offset: u32,
     /// Get the character length of this line.
     pub length: u32,
-    /// Get the view of this line in the original [`SourceText`].
-    pub text: String,
+    /// Get the range of this line in the original [`SourceText`], excluding trailing whitespace and line terminators.
+    pub text: Range<u32>,
+    /// Get the terminator this line ends with, see [`SourceLine::ending_range`].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ending: LineEnding,
 }
 
 /// A type representing a single line of a [`Source`].
@@ -60,6 +93,36 @@ impl SourceText {
         src.path = SourcePath::Snippet(name.into());
         src
     }
+    /// Memory-map a local file, the lines are only indexed up to the furthest offset that gets queried.
+    ///
+    /// The file is checked to be valid UTF-8 when mapped, but not split into lines.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the returned [`SourceText`] or any clone of it is alive,
+    /// otherwise the text may change under the line index or stop being valid UTF-8.
+    pub unsafe fn mapped(path: &Path) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        let modified = file.metadata()?.modified().ok();
+        let raw = SourceBuffer::map(&file)?;
+        let length = raw.len();
+        Ok(Self {
+            path: SourcePath::Local(path.to_path_buf()),
+            raw,
+            lines: LineIndex::lazy(length, LAZY_BLOCK_SIZE),
+            length: length as u32,
+            dirty: false,
+            modified,
+            hash: OnceLock::new(),
+            decoding: None,
+        })
+    }
+    /// Read a local file, remembering its modification time so changes can be detected later.
+    ///
+    /// The encoding is detected from the byte order mark, falling back to UTF-8, see [`TextDecoder`].
+    pub fn read(path: &Path) -> std::io::Result<Self> {
+        Self::read_with(path, &TextDecoder::default())
+    }
 
     /// Get the cache id
     pub fn source_id(&self) -> SourceID {
@@ -71,7 +134,33 @@ impl SourceText {
     }
     /// Get access to a specific, zero-indexed [`SourceLine`].
     pub fn get_line(&self, idx: usize) -> Option<&SourceLine> {
-        self.lines.get(idx)
+        self.lines.get(&self.raw, idx)
+    }
+    /// Get the text of a specific, zero-indexed line, excluding trailing whitespace and line terminators.
+    pub fn get_line_text(&self, idx: usize) -> Option<&str> {
+        Some(self.get_line(idx)?.view(self))
+    }
+    /// Get the modification time of the file when it was read, if the text came from the file system.
+    pub fn get_modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+    /// Get the hash of the text, used to tell whether the content of a file really changed.
+    ///
+    /// This is the 64-bit FNV-1a hash of the UTF-8 text, it is stable across runs and machines.
+    pub fn get_content_hash(&self) -> u64 {
+        *self.hash.get_or_init(|| {
+            let mut hasher = StableHasher::default();
+            hasher.write(self.text().as_bytes());
+            hasher.finish()
+        })
+    }
+    /// Check whether the text is out of date with where it was loaded from.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+    /// Mark the text as out of date with where it was loaded from.
+    pub fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
     }
     /// Get the length of the total number of characters in the identifier.
     pub fn get_source(&self) -> &SourcePath {
@@ -101,28 +190,70 @@ impl SourceText {
 
     /// Return the raw text fetch from source
     pub fn text(&self) -> &str {
-        self.raw.as_str()
+        &self.raw
     }
     /// Return an iterator over the [`SourceLine`]s in this identifier.
-    pub fn lines(&self) -> &[SourceLine] {
-        self.lines.as_slice()
+    ///
+    /// This indexes the whole text if it was loaded lazily.
+    pub fn lines(&self) -> impl Iterator<Item = &SourceLine> + '_ {
+        self.lines.iter(&self.raw)
+    }
+    /// Get the number of lines in this identifier.
+    ///
+    /// This indexes the whole text if it was loaded lazily.
+    pub fn get_line_count(&self) -> usize {
+        self.lines.count(&self.raw)
+    }
+    /// Use a line table computed earlier for the same text, instead of splitting the text again.
+    ///
+    /// The text must be the one the table was made for, as checked by its hash. Returns `false` and keeps the current
+    /// table if the lines do not fit the text.
+    pub(crate) fn restore_lines(&mut self, lines: Box<[SourceLine]>) -> bool {
+        match LineIndex::restored(&self.raw, lines) {
+            Some(index) => {
+                self.lines = index;
+                true
+            }
+            None => false,
+        }
+    }
+    /// Get the number of bytes, from the start of the text, that have been split into lines so far.
+    pub fn get_indexed_length(&self) -> usize {
+        self.lines.indexed(&self.raw)
     }
     /// Clear the cache cache
     pub fn clear(&mut self) {
-        self.raw.clear();
-        self.lines.clear();
+        self.raw = SourceBuffer::default();
+        self.lines = LineIndex::eager("");
+        self.length = 0;
         self.dirty = true;
+        self.hash = OnceLock::new();
+        self.decoding = None;
     }
 }
+impl PartialEq for SourceText {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path && self.text() == other.text() && self.dirty == other.dirty
+    }
+}
+
+impl Eq for SourceText {}
+
+impl Hash for SourceText {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+        self.text().hash(state);
+        self.dirty.hash(state);
+    }
+}
+
 impl SourceText {
     /// Get the line that the given offset appears on, and the line/column numbers of the offset.
     ///
     /// Note that the line/column numbers are zero-indexed.
     pub fn get_offset_line(&self, offset: u32) -> Option<(&SourceLine, usize, u32)> {
         if offset <= self.length {
-            let idx = self.lines.binary_search_by_key(&offset, |line| line.offset).unwrap_or_else(|idx| idx.saturating_sub(1));
-            let line = &self.lines[idx];
-            assert!(offset >= line.offset, "offset = {}, line.offset = {}", offset, line.offset);
+            let (idx, line) = self.lines.find(&self.raw, offset)?;
             Some((line, idx, offset - line.offset))
         }
         else {
@@ -135,7 +266,9 @@ impl SourceText {
     /// [`SourceText::get_line`]).
     pub fn get_line_range(&self, span: &Range<u32>) -> Range<usize> {
         let start = self.get_offset_line(span.start).map_or(0, |(_, l, _)| l);
-        let end = self.get_offset_line(span.end.saturating_sub(1).max(span.start)).map_or(self.lines.len(), |(_, l, _)| l + 1);
+        let end = self
+            .get_offset_line(span.end.saturating_sub(1).max(span.start))
+            .map_or_else(|| self.get_line_count(), |(_, l, _)| l + 1);
         start..end
     }
 }
@@ -200,9 +333,12 @@ impl SourceLine {
     pub fn range(&self) -> Range<u32> {
         self.offset..self.offset + self.length
     }
-
+    /// Borrow the text of this line from the [`SourceText`] it belongs to, excluding trailing whitespace.
+    pub fn view<'a>(&self, source: &'a SourceText) -> &'a str {
+        &source.text()[self.text.start as usize..self.text.end as usize]
+    }
     /// Return an iterator over the characters in the line, excluding trailing whitespace.
-    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
-        self.text.chars()
+    pub fn chars<'a>(&self, source: &'a SourceText) -> impl Iterator<Item = char> + 'a {
+        self.view(source).chars()
     }
 }
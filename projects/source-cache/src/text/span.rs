@@ -0,0 +1,95 @@
+use super::*;
+
+impl SourceSpan {
+    /// Create a span from zero-based `(line, column)` pairs, where columns are byte offsets into the line as given by
+    /// [`SourceText::get_offset_line`].
+    ///
+    /// Returns `None` if a line does not exist, a column runs past the end of its line, or the end is before the start.
+    pub fn from_line_column(source: &SourceText, start: (usize, u32), end: (usize, u32)) -> Option<Self> {
+        let offset = |(line, column): (usize, u32)| {
+            let line = source.get_line(line)?;
+            (column <= line.length).then_some(line.offset + column)
+        };
+        let (start, end) = (offset(start)?, offset(end)?);
+        (start <= end).then_some(Self { start, end, file: source.source_id() })
+    }
+
+    /// Determine whether the span is in the same file and covers all of the other span.
+    pub fn contains_span(&self, other: &Self) -> bool {
+        self.file == other.file && self.start <= other.start && other.end <= self.end
+    }
+    /// Determine whether the spans are in the same file and share at least one offset.
+    ///
+    /// An empty span counts as covering its offset, so it overlaps the spans containing that offset.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        let end = |span: &Self| span.end.max(span.start.saturating_add(1));
+        self.file == other.file && self.start < end(other) && other.start < end(self)
+    }
+    /// Get the smallest span covering both spans, returns `None` if they are in different files.
+    pub fn join(&self, other: &Self) -> Option<Self> {
+        if self.file != other.file {
+            return None;
+        }
+        Some(Self { start: self.start.min(other.start), end: self.end.max(other.end), file: self.file })
+    }
+    /// Get the part covered by both spans, returns `None` if they are in different files or are apart.
+    ///
+    /// Spans that touch intersect in an empty span.
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        (self.file == other.file && start <= end).then_some(Self { start, end, file: self.file })
+    }
+
+    /// Move the start forward and the end backward, the span collapses at its new start rather than inverting.
+    pub fn shrink(&self, start: u32, end: u32) -> Self {
+        let new_start = self.start.saturating_add(start).min(self.end);
+        Self { start: new_start, end: self.end.saturating_sub(end).max(new_start), file: self.file }
+    }
+    /// Move the start backward and the end forward, stopping at the bounds of the offsets.
+    pub fn expand(&self, start: u32, end: u32) -> Self {
+        Self { start: self.start.saturating_sub(start), end: self.end.saturating_add(end), file: self.file }
+    }
+    /// Move the span by the offset, returns `None` if it would move out of the bounds of the offsets.
+    pub fn shift(&self, offset: i64) -> Option<Self> {
+        let shift = |x: u32| u32::try_from(x as i64 + offset).ok();
+        Some(Self { start: shift(self.start)?, end: shift(self.end)?, file: self.file })
+    }
+
+    /// Shrink the span to exclude whitespace at both ends of its text.
+    ///
+    /// Returns `None` if the span is not in the source or does not fall on character boundaries. A span of only
+    /// whitespace collapses to an empty span at its end.
+    pub fn trim(&self, source: &SourceText) -> Option<Self> {
+        let text = self.text(source)?;
+        let start = self.start + (text.len() - text.trim_start().len()) as u32;
+        let end = start + text.trim().len() as u32;
+        Some(Self { start, end, file: self.file })
+    }
+    /// Cut the span where lines of the source start, the parts cover the span in order and keep the line terminators.
+    ///
+    /// Returns `None` if the span is not in the source or runs past its end. An empty span is returned as it is.
+    pub fn split_lines(&self, source: &SourceText) -> Option<Vec<Self>> {
+        if source.source_id() != self.file || self.end > source.length || self.start > self.end {
+            return None;
+        }
+        if self.start == self.end {
+            return Some(vec![*self]);
+        }
+        let lines = source.get_line_range(&self.get_range());
+        let parts = lines.filter_map(|line| {
+            let range = source.get_line(line)?.range();
+            let start = range.start.max(self.start);
+            let end = range.end.min(self.end);
+            (start < end).then_some(Self { start, end, file: self.file })
+        });
+        Some(parts.collect())
+    }
+    /// Borrow the text of the span from the source, returns `None` if the span is not in the source.
+    pub fn text<'a>(&self, source: &'a SourceText) -> Option<&'a str> {
+        if source.source_id() != self.file {
+            return None;
+        }
+        source.text().get(self.start as usize..self.end as usize)
+    }
+}
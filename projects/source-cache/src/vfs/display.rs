@@ -0,0 +1,12 @@
+use super::*;
+use std::fmt::{Debug, Formatter};
+
+impl Debug for VirtualFileSystem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let buffers = self.buffers.read().expect("buffers are poisoned");
+        f.debug_struct("VirtualFileSystem")
+            .field("buffers", &buffers.keys().collect::<Vec<_>>())
+            .field("layers", &self.layers.len())
+            .finish()
+    }
+}
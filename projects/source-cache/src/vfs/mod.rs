@@ -0,0 +1,166 @@
+use crate::{SourceText, TextDecoder};
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+mod display;
+
+/// A read-only source of files for a [`VirtualFileSystem`], such as the real file system or an archive.
+///
+/// Layers are stacked, a path is served by the topmost layer that has it.
+pub trait FileLayer: Send + Sync {
+    /// Read the file at the path as a local [`SourceText`], returns `None` if this layer does not have it so the layers
+    /// below are asked instead.
+    fn read(&self, path: &Path) -> Option<Result<SourceText, Error>>;
+}
+
+/// The real file system, the default bottom layer of a [`VirtualFileSystem`].
+///
+/// Files are decoded with the [`TextDecoder`] it was created with, which detects UTF-8 and UTF-16 by default.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FileSystem {
+    decoder: TextDecoder,
+}
+
+/// Resolves local paths through an overlay of unsaved buffers on top of a stack of [`FileLayer`]s.
+///
+/// Every text it reads is a [`SourcePath::Local`](crate::SourcePath::Local), so an unsaved buffer and the file saved
+/// at the same path get the same [`SourceID`](crate::SourceID). Clones copy the buffers, use
+/// [`VirtualFileSystem::share`] for a handle an editor can keep to update the buffers of the cache it was given to.
+///
+/// ```
+/// # use source_cache::{SourceCache, VirtualFileSystem};
+/// let mut cache = SourceCache::default();
+/// let file = cache.open_buffer("src/main.x", "let x = 1;").unwrap();
+/// assert_eq!(cache.fetch(&file).unwrap().text(), "let x = 1;");
+/// ```
+pub struct VirtualFileSystem {
+    buffers: Arc<RwLock<HashMap<PathBuf, String>>>,
+    /// The layers below the buffers, topmost first
+    layers: Vec<Arc<dyn FileLayer>>,
+}
+
+impl<F> FileLayer for F
+where
+    F: Fn(&Path) -> Option<Result<SourceText, Error>> + Send + Sync,
+{
+    fn read(&self, path: &Path) -> Option<Result<SourceText, Error>> {
+        self(path)
+    }
+}
+
+impl FileSystem {
+    /// Read files with the given decoder, for example to read legacy files as Latin-1.
+    pub fn new(decoder: TextDecoder) -> Self {
+        Self { decoder }
+    }
+}
+
+impl FileLayer for FileSystem {
+    fn read(&self, path: &Path) -> Option<Result<SourceText, Error>> {
+        match SourceText::read_with(path, &self.decoder) {
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            result => Some(result),
+        }
+    }
+}
+
+impl VirtualFileSystem {
+    /// Create a file system without any layers, where only the buffers can be read.
+    pub fn empty() -> Self {
+        Self { buffers: Arc::default(), layers: vec![] }
+    }
+    /// Put a layer on top of the existing layers, below the buffers.
+    pub fn push_layer<L>(&mut self, layer: L)
+    where
+        L: FileLayer + 'static,
+    {
+        self.layers.insert(0, Arc::new(layer));
+    }
+    /// Put a layer on top of the existing layers, below the buffers.
+    pub fn with_layer<L>(mut self, layer: L) -> Self
+    where
+        L: FileLayer + 'static,
+    {
+        self.push_layer(layer);
+        self
+    }
+    /// Get a handle on the same buffers and layers, buffers opened or closed through either are seen by both.
+    ///
+    /// ```
+    /// # use source_cache::{SourceCache, VirtualFileSystem};
+    /// let editor = VirtualFileSystem::default();
+    /// let mut cache = SourceCache::default();
+    /// cache.set_vfs(editor.share());
+    /// editor.open_buffer("src/main.x", "let x = 1;");
+    /// let file = cache.load_local("src/main.x").unwrap();
+    /// assert_eq!(cache.fetch(&file).unwrap().text(), "let x = 1;");
+    /// ```
+    pub fn share(&self) -> Self {
+        Self { buffers: self.buffers.clone(), layers: self.layers.clone() }
+    }
+    /// Get the number of layers below the buffers.
+    pub fn get_layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Add or replace the unsaved buffer at the path, returns the previous buffer.
+    pub fn open_buffer<P, S>(&self, path: P, text: S) -> Option<String>
+    where
+        P: AsRef<Path>,
+        S: Into<String>,
+    {
+        self.buffers.write().expect("buffers are poisoned").insert(path.as_ref().to_path_buf(), text.into())
+    }
+    /// Remove the unsaved buffer at the path, so the layers below show through again, returns the removed buffer.
+    pub fn close_buffer<P>(&self, path: P) -> Option<String>
+    where
+        P: AsRef<Path>,
+    {
+        self.buffers.write().expect("buffers are poisoned").remove(path.as_ref())
+    }
+    /// Check whether the path has an unsaved buffer.
+    pub fn has_buffer<P>(&self, path: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        self.buffers.read().expect("buffers are poisoned").contains_key(path.as_ref())
+    }
+
+    /// Read the file at the path from the buffer or the topmost layer that has it.
+    ///
+    /// Texts read from a buffer have no modification time, see [`SourceText::get_modified`].
+    pub fn read<P>(&self, path: P) -> Result<SourceText, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if let Some(text) = self.buffers.read().expect("buffers are poisoned").get(path) {
+            return Ok(SourceText::from(text.clone()).with_path(path));
+        }
+        for layer in &self.layers {
+            if let Some(result) = layer.read(path) {
+                return result.map(|text| text.with_path(path));
+            }
+        }
+        Err(Error::new(ErrorKind::NotFound, format!("{} not found in any layer", path.display())))
+    }
+}
+
+/// Clones get a copy of the buffers, so a cloned [`SourceCache`](crate::SourceCache) does not see the buffers opened in
+/// the original, see [`VirtualFileSystem::share`].
+impl Clone for VirtualFileSystem {
+    fn clone(&self) -> Self {
+        let buffers = self.buffers.read().expect("buffers are poisoned").clone();
+        Self { buffers: Arc::new(RwLock::new(buffers)), layers: self.layers.clone() }
+    }
+}
+
+impl Default for VirtualFileSystem {
+    fn default() -> Self {
+        Self::empty().with_layer(FileSystem::default())
+    }
+}
@@ -0,0 +1,112 @@
+use super::*;
+use source_cache::SourcePath;
+use std::{io::ErrorKind, path::Path};
+
+#[cfg(any(feature = "zip", feature = "tar"))]
+fn temp_archive(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("source-cache-{}-{}", std::process::id(), name))
+}
+
+#[cfg(feature = "tar")]
+fn write_tar<W: std::io::Write>(writer: W) {
+    let mut builder = tar::Builder::new(writer);
+    for (name, data) in [("./src/main.x", &b"let x = 1;\n"[..]), ("logo.png", &[0x89, b'P', b'N', b'G', 0xFF][..])] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data).unwrap();
+    }
+    builder.into_inner().unwrap();
+}
+
+#[test]
+#[cfg(feature = "zip")]
+fn zip_archives() {
+    use source_cache::PathRemap;
+    use std::io::Write;
+    let path = temp_archive("package.zip");
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+    zip.add_directory("src/", Default::default()).unwrap();
+    zip.start_file("src/main.x", Default::default()).unwrap();
+    zip.write_all(b"let x = 1;\n").unwrap();
+    zip.start_file("readme.md", Default::default()).unwrap();
+    zip.write_all(b"# Package\n").unwrap();
+    zip.finish().unwrap();
+
+    let mut cache = SourceCache::default();
+    let files = cache.load_archive(&path).unwrap();
+    assert_eq!(files.len(), 2);
+    let main = SourcePath::Archive { archive: path.clone(), entry: "src/main.x".to_string() };
+    assert_eq!(files[0], main.source_id());
+    assert_eq!(cache.fetch(&files[0]).unwrap().text(), "let x = 1;\n");
+    assert_eq!(cache.load_archived(&path, "./readme.md").unwrap(), files[1]);
+    assert_eq!(cache.load_archived(&path, "missing.x").unwrap_err().kind(), ErrorKind::NotFound);
+
+    cache.set_path_remap(PathRemap::default().with_root(path.parent().unwrap()));
+    let name = path.file_name().unwrap().to_string_lossy();
+    assert_eq!(cache.display_path(&files[0]), Some(format!("{}!/src/main.x", name)));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn tar_archives() {
+    let plain = temp_archive("package.tar");
+    write_tar(std::fs::File::create(&plain).unwrap());
+    let gzip = temp_archive("package.tar.gz");
+    write_tar(flate2::write::GzEncoder::new(std::fs::File::create(&gzip).unwrap(), Default::default()));
+
+    for path in [&plain, &gzip] {
+        let mut cache = SourceCache::default();
+        // The binary file is left out
+        let files = cache.load_archive(path).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            cache.source_path(&files[0]).unwrap().to_string(),
+            format!("{}!/src/main.x", SourcePath::Local(path.clone()))
+        );
+        assert_eq!(cache.load_archived(path, "logo.png").unwrap_err().kind(), ErrorKind::InvalidData);
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn unknown_archives() {
+    let mut cache = SourceCache::default();
+    let error = cache.load_archive(Path::new("package.rar")).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::Unsupported);
+    assert_eq!(SourcePath::Archive { archive: "a.zip".into(), entry: "b".into() }.to_string(), "a.zip!/b");
+}
+
+#[test]
+#[cfg(feature = "tar")]
+fn untrusted_sizes() {
+    use std::io::{Read, Write};
+    // A header that claims a terabyte for a few bytes of text
+    let path = temp_archive("claims.tar");
+    let mut header = tar::Header::new_gnu();
+    header.set_path("huge.x").unwrap();
+    header.set_size(1 << 40);
+    header.set_cksum();
+    let mut file = std::fs::File::create(&path).unwrap();
+    file.write_all(header.as_bytes()).unwrap();
+    file.write_all(&[b'x'; 512]).unwrap();
+    drop(file);
+    let mut cache = SourceCache::default();
+    assert!(cache.load_archive(&path).is_err());
+
+    // A file that really is too large is not read whole
+    let mut builder =
+        tar::Builder::new(flate2::write::GzEncoder::new(std::fs::File::create(&path).unwrap(), Default::default()));
+    let mut header = tar::Header::new_gnu();
+    header.set_size((64 << 20) + 1);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, "spaces.x", std::io::repeat(b' ').take((64 << 20) + 1)).unwrap();
+    builder.into_inner().unwrap().finish().unwrap();
+    let gzip = path.with_extension("tgz");
+    std::fs::rename(&path, &gzip).unwrap();
+    assert_eq!(cache.load_archive(&gzip).unwrap_err().kind(), ErrorKind::InvalidData);
+    std::fs::remove_file(gzip).unwrap();
+}
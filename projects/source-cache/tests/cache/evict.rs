@@ -0,0 +1,95 @@
+use super::*;
+use source_cache::CacheCapacity;
+use std::path::Path;
+
+#[test]
+fn least_recently_used_local_sources() {
+    let paths: Vec<_> = ["a", "b", "c"].iter().map(|n| write_temp(&format!("evict-{}.x", n), n)).collect();
+    let mut cache = SourceCache::default().with_capacity(CacheCapacity::Entries(3));
+    let snippet = cache.load_text("pinned", "<repl>").unwrap();
+    let a = cache.load_local(&paths[0]).unwrap();
+    let b = cache.load_local(&paths[1]).unwrap();
+    cache.fetch(&a).unwrap();
+    // `b` is the least recently used source that can be read again
+    let c = cache.load_local(&paths[2]).unwrap();
+    // Only evicted sources are read again, with the content their file has then
+    for (path, text) in paths.iter().zip(["a2", "b2", "c2"]) {
+        std::fs::write(path, text).unwrap();
+    }
+    assert_eq!(cache.fetch(&c).unwrap().text(), "c");
+    assert_eq!(cache.fetch(&b).unwrap().text(), "b2");
+    assert_eq!(cache.fetch(&a).unwrap().text(), "a");
+    // `c` and `b` are now the least recently used, the snippet can not go
+    cache.set_capacity(Some(CacheCapacity::Entries(2)));
+    assert_eq!(cache.fetch(&a).unwrap().text(), "a");
+    assert_eq!(cache.fetch(&snippet).unwrap().text(), "pinned");
+    assert_eq!(cache.fetch(&c).unwrap().text(), "c2");
+    // Evicted sources read again count from the next eviction on
+    assert_eq!(cache.shrink_to_capacity(), vec![a]);
+    for (file, text) in [(a, "a2"), (b, "b2"), (c, "c2")] {
+        assert_eq!(cache.fetch(&file).unwrap().text(), text);
+    }
+    paths.into_iter().for_each(|p| std::fs::remove_file(p).unwrap());
+}
+
+#[test]
+fn texts_not_read_from_files_are_pinned() {
+    let mut cache = SourceCache::default().with_capacity(CacheCapacity::Entries(1));
+    let a = cache.insert(SourceText::from("generated a").with_path(Path::new("/nonexistent/a.x"))).unwrap();
+    let b = cache.insert(SourceText::from("generated b").with_path(Path::new("/nonexistent/b.x"))).unwrap();
+    assert_eq!(cache.shrink_to_capacity(), vec![]);
+    assert_eq!(cache.fetch(&a).unwrap().text(), "generated a");
+    assert_eq!(cache.fetch(&b).unwrap().text(), "generated b");
+}
+
+#[test]
+fn byte_capacity() {
+    let long = write_temp("evict-long.x", &"x".repeat(100));
+    let short = write_temp("evict-short.x", "y");
+    let mut cache = SourceCache::default();
+    let long_id = cache.load_local(&long).unwrap();
+    // Setting the capacity evicts the long file right away
+    cache.set_capacity(Some(CacheCapacity::Bytes(50)));
+    let short_id = cache.load_local(&short).unwrap();
+    assert_eq!(cache.shrink_to_capacity(), vec![]);
+    // Evicted files are read as they are when fetched again
+    std::fs::write(&long, "changed").unwrap();
+    assert_eq!(cache.fetch(&long_id).unwrap().text(), "changed");
+    assert_eq!(cache.fetch(&short_id).unwrap().text(), "y");
+    assert_eq!(cache.get_capacity(), Some(CacheCapacity::Bytes(50)));
+    std::fs::remove_file(long).unwrap();
+    std::fs::remove_file(short).unwrap();
+}
+
+#[test]
+fn refresh_evicted_sources() {
+    let path = write_temp("evict-refresh.x", "old");
+    let buffer = std::env::temp_dir().join("evict-buffer.x");
+    let mut cache = SourceCache::default().with_capacity(CacheCapacity::Entries(1));
+    let file = cache.load_local(&path).unwrap();
+    assert_eq!(cache.fetch(&file).unwrap().text(), "old");
+    // Unsaved buffers are never evicted, the file is
+    let unsaved = cache.open_buffer(&buffer, "unsaved").unwrap();
+    assert_eq!(cache.shrink_to_capacity(), vec![]);
+    assert_eq!(cache.fetch(&file).unwrap().text(), "old");
+
+    std::fs::write(&path, "new text").unwrap();
+    // Make sure the modification time differs on file systems with a coarse clock
+    let time = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+    std::fs::File::options().write(true).open(&path).unwrap().set_modified(time).unwrap();
+    assert_eq!(cache.refresh(), vec![file]);
+    assert_eq!(cache.fetch(&file).unwrap().text(), "new text");
+    assert_eq!(cache.fetch(&unsaved).unwrap().text(), "unsaved");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn refresh_deferred_sources() {
+    let path = write_temp("evict-deferred.x", "new");
+    let mut cache = SourceCache::default();
+    let file = cache.load_deferred(&path, SourceText::from("old").get_content_hash()).unwrap();
+    assert_eq!(cache.fetch(&file).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(cache.refresh(), vec![file]);
+    assert_eq!(cache.fetch(&file).unwrap().text(), "new");
+    std::fs::remove_file(path).unwrap();
+}
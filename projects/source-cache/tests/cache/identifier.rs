@@ -0,0 +1,42 @@
+use super::*;
+use source_cache::{SourcePath, Url};
+use std::{io::ErrorKind, path::Path};
+
+#[test]
+fn ids_are_stable() {
+    let id = |path: SourcePath| format!("{:?}", path.source_id());
+    assert_eq!(id(SourcePath::Anonymous), "FileID(0xAF63BD4C8601B7DF)");
+    assert_eq!(id(SourcePath::Snippet("snippet".into())), "FileID(0x1EEC3C7FC8165AE1)");
+    assert_eq!(id(SourcePath::Local(Path::new("src/main.rs").to_path_buf())), "FileID(0x545F416C5E285ABC)");
+    assert_eq!(id(SourcePath::Remote(Url::parse("https://example.com/lib.x").unwrap())), "FileID(0x155EF3C4D4DBC908)");
+    assert_eq!(SourceText::from("hello").get_content_hash(), 0xA430_D846_80AA_BD0B);
+}
+
+#[test]
+fn collisions_are_reported() {
+    let mut cache = SourceCache::default();
+    let file = cache.load_text("a", "one").unwrap();
+    // Reinserting the same path replaces the text
+    assert_eq!(cache.insert(SourceText::snippet("b", "one")).unwrap(), file);
+    assert_eq!(cache.fetch(&file).unwrap().text(), "b");
+    // Forge a collision by renaming the cached source behind its id
+    assert!(unsafe { cache.set_source(file, "two") });
+    let error = cache.insert(SourceText::snippet("c", "one")).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+    assert_eq!(cache.fetch(&file).unwrap().text(), "b");
+    assert_eq!(cache.load_text("d", "one").unwrap_err().kind(), ErrorKind::AlreadyExists);
+}
+
+#[test]
+fn embedded_regions() {
+    let mut cache = SourceCache::default();
+    let outer = cache.load_text("<script>let x = 1;</script>", "page.html").unwrap();
+    let script = cache.add_embedded(outer.with_range(8..18)).unwrap();
+    assert_eq!(script, SourcePath::Embedded(outer.with_range(8..18)).source_id());
+    assert_ne!(script, cache.add_embedded(outer.with_range(8..17)).unwrap());
+    assert_eq!(cache.display_path(&script), Some("page.html".to_string()));
+    assert_eq!(cache.resolve_embedded(script.with_range(4..5)), outer.with_range(12..13));
+    assert_eq!(cache.resolve_embedded(outer.with_range(4..5)), outer.with_range(4..5));
+    let error = cache.add_embedded(outer.with_range(20..40)).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+}
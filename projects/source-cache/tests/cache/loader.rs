@@ -0,0 +1,61 @@
+use super::*;
+use source_cache::{SourcePath, Url, VirtualLoader};
+use std::{
+    io::{Error, ErrorKind},
+    sync::Arc,
+};
+
+#[test]
+fn load_file_url() {
+    let path = write_temp("loader.txt", "from disk");
+    let mut cache = SourceCache::default();
+    let file = cache.load_remote(Url::from_file_path(&path).unwrap()).unwrap();
+    assert_eq!(file, cache.load_local(&path).unwrap());
+    assert_eq!(cache.fetch(&file).unwrap().text(), "from disk");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn load_data_url() {
+    let mut cache = SourceCache::default();
+    let plain = cache.load_remote(Url::parse("data:,let%20a%20%3D%201").unwrap()).unwrap();
+    assert_eq!(cache.fetch(&plain).unwrap().text(), "let a = 1");
+    let base64 = cache.load_remote(Url::parse("data:text/plain;charset=utf-8;base64,bGV0IGIgPSAy").unwrap()).unwrap();
+    assert_eq!(cache.fetch(&base64).unwrap().text(), "let b = 2");
+    let query = cache.load_remote(Url::parse("data:,a?b%3Fc#d").unwrap()).unwrap();
+    assert_eq!(cache.fetch(&query).unwrap().text(), "a?b?c#d");
+    let error = cache.load_remote(Url::parse("data:;base64,/w==").unwrap()).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn load_custom_scheme() {
+    let mut cache = SourceCache::default();
+    let url = Url::parse("https://example.com/main.x").unwrap();
+    assert_eq!(cache.load_remote(url.clone()).unwrap_err().kind(), ErrorKind::Unsupported);
+
+    // A local stand-in for an HTTP client
+    cache.add_loader("https", |url: &Url| match url.path() {
+        "/main.x" => Ok("fetched".to_string()),
+        _ => Err(Error::new(ErrorKind::NotFound, url.as_str())),
+    });
+    let file = cache.load_remote(url.clone()).unwrap();
+    assert_eq!(cache.fetch(&file).unwrap().text(), "fetched");
+    assert_eq!(cache.source_path(&file), Some(&SourcePath::Remote(url)));
+    let missing = Url::parse("https://example.com/missing.x").unwrap();
+    assert_eq!(cache.load_remote(missing).unwrap_err().kind(), ErrorKind::NotFound);
+}
+
+#[test]
+fn load_virtual_files() {
+    let files = Arc::new(VirtualLoader::default());
+    let mut cache = SourceCache::default();
+    cache.add_loader("mem", files.clone());
+
+    let url = Url::parse("mem:///lib/prelude.x").unwrap();
+    assert_eq!(cache.load_remote(url.clone()).unwrap_err().kind(), ErrorKind::NotFound);
+    files.insert(url.clone(), "prelude");
+    let file = cache.load_remote(url.clone()).unwrap();
+    assert_eq!(cache.fetch(&file).unwrap().text(), "prelude");
+    assert_eq!(files.remove(&url).as_deref(), Some("prelude"));
+}
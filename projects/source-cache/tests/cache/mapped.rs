@@ -0,0 +1,64 @@
+use super::*;
+
+const BLOCK: usize = 64 * 1024;
+
+/// Lines of every kind, with terminators placed across the lazy block boundaries
+fn sample() -> String {
+    let endings = ["\n", "\r\n", "\r", "\x0B", "\x0C", "\u{0085}", "\u{2028}", "\u{2029}"];
+    let mut text = String::new();
+    let mut i = 0;
+    while text.len() < 4 * BLOCK {
+        text.push_str(&"x".repeat(i % 97));
+        text.push_str(endings[i % endings.len()]);
+        i += 1;
+    }
+    for boundary in [BLOCK, 2 * BLOCK, 3 * BLOCK] {
+        let at = boundary - 1;
+        text.replace_range(at..at + 2, "\r\n");
+    }
+    // a single line longer than a block
+    text.push_str(&"y".repeat(BLOCK + 10));
+    text.push_str("\u{2028}tail");
+    text
+}
+
+#[test]
+fn mapped_matches_eager() {
+    let text = sample();
+    let path = write_temp("eager.txt", &text);
+    let mapped = unsafe { SourceText::mapped(&path) }.unwrap();
+    let eager = SourceText::from(text.clone());
+
+    assert_eq!(mapped.text(), eager.text());
+    assert_eq!(mapped.get_line_count(), eager.get_line_count());
+    assert!(mapped.lines().eq(eager.lines()));
+    for offset in
+        (0..=text.len() as u32).step_by(131).chain((1..5).flat_map(|b| (b * BLOCK - 4..b * BLOCK + 4).map(|o| o as u32)))
+    {
+        let lazy = mapped.get_offset_line(offset).map(|(line, idx, col)| (line.clone(), idx, col));
+        let full = eager.get_offset_line(offset).map(|(line, idx, col)| (line.clone(), idx, col));
+        assert_eq!(lazy, full, "offset {}", offset);
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn mapped_indexes_lazily() {
+    let text = sample();
+    let path = write_temp("lazy.txt", &text);
+    let mut cache = SourceCache::default();
+    let file = unsafe { cache.load_mapped(&path) }.unwrap();
+    let source = cache.fetch(&file).unwrap();
+    assert_eq!(source.get_indexed_length(), 0);
+
+    let (line, idx, column) = source.get_offset_line(100).unwrap();
+    assert_eq!((idx, column), (13, 2));
+    assert_eq!(line.view(source), "x".repeat(13));
+    assert_eq!(source.get_indexed_length(), BLOCK);
+
+    let (line, idx, _) = source.get_offset_line(2 * BLOCK as u32 + 7).unwrap();
+    assert_eq!(source.get_line(idx), Some(line));
+    assert_eq!(source.get_indexed_length(), 3 * BLOCK);
+    assert_eq!(source.get_line_text(0), Some(""));
+    std::fs::remove_file(path).unwrap();
+}
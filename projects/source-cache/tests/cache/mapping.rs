@@ -0,0 +1,52 @@
+use super::*;
+use source_cache::SourceMap;
+
+#[test]
+fn maps_are_chained() {
+    let mut cache = SourceCache::default();
+    let template = cache.load_text("{{ a + b }}", "sum.tpl").unwrap();
+    let script = cache.load_text("emit(a + b)", "sum.js").unwrap();
+    let bytecode = cache.load_text("push a\npush b\nadd\ncall emit", "sum.bc").unwrap();
+    let mut map = SourceMap::new(script);
+    map.add_mapping(5, template, 3);
+    map.add_unmapped(10);
+    cache.add_source_map(map);
+    let mut map = SourceMap::new(bytecode);
+    map.add_mapping(0, script, 5);
+    map.add_mapping(14, script, 7);
+    map.add_mapping(18, script, 0);
+    cache.add_source_map(map);
+
+    assert_eq!(cache.map_span(bytecode.with_range(14..17)), Some(template.with_range(5..8)));
+    // The call is glue code of the script
+    assert_eq!(cache.map_span(bytecode.with_range(18..22)), Some(script.with_range(0..4)));
+    assert_eq!(cache.map_span(script.with_range(0..4)), None);
+    assert_eq!(cache.map_span(template.with_range(0..2)), None);
+}
+
+#[test]
+#[cfg(feature = "source-map")]
+fn loads_v3_maps() {
+    use source_cache::SourcePath;
+    let mut cache = SourceCache::default();
+    let output = cache.load_text("var x = 1;\nvar y = '😀' + 2;\n", "out.js").unwrap();
+    let json = r#"{
+        "version": 3,
+        "file": "out.js",
+        "sources": ["in.ts"],
+        "sourcesContent": ["let x = 1;\nlet y = '😀' + 2;\n"],
+        "names": [],
+        "mappings": "AAAA,IAAI;AACJ,IAAI,MAAM"
+    }"#;
+    cache.load_source_map(output, json).unwrap();
+    let map = cache.get_source_map(&output).unwrap();
+    assert_eq!(map.get_segment_count(), 5);
+    let y = cache.map_span(output.with_range(15..16)).unwrap();
+    assert_eq!(y, y.file.with_range(15..16));
+    assert_eq!(cache.source_path(&y.file), Some(&SourcePath::Local("in.ts".into())));
+    // Columns count UTF-16 units, the emoji is 2 units but 4 bytes
+    let plus = cache.map_span(output.with_range(26..27)).unwrap();
+    assert_eq!(cache.fetch(&plus.file).unwrap().text()[plus.start as usize..plus.end as usize].to_string(), "+");
+    assert!(cache.load_source_map(output, r#"{"version": 2, "mappings": ""}"#).is_err());
+    assert!(cache.load_source_map(output, r#"{"version": 3, "mappings": "A!"}"#).is_err());
+}
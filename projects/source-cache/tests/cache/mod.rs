@@ -0,0 +1,21 @@
+use source_cache::{SourceCache, SourceText};
+use std::path::PathBuf;
+
+mod archive;
+mod evict;
+mod identifier;
+mod loader;
+mod mapped;
+mod mapping;
+mod notebook;
+mod remap;
+mod shared;
+mod snapshot;
+mod vfs;
+mod watch;
+
+fn write_temp(name: &str, text: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("source-cache-{}-{}", std::process::id(), name));
+    std::fs::write(&path, text).unwrap();
+    path
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "notebook")]
+use super::*;
+use source_cache::SourcePath;
+
+const NOTEBOOK: &str = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Title"]},
+  {"cell_type": "code", "metadata": {}, "outputs": [], "source": ["x = \"café\"\n", "y = x.uper()"]},
+  {"cell_type": "code", "metadata": {}, "outputs": [], "source": "z = 1"}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+#[test]
+fn code_cells() {
+    let path = write_temp("analysis.ipynb", NOTEBOOK);
+    let mut cache = SourceCache::default();
+    let cells = cache.load_notebook(&path).unwrap();
+    assert_eq!(cells.len(), 2);
+    let first = cache.fetch(&cells[0]).unwrap();
+    assert_eq!(first.text(), "x = \"café\"\ny = x.uper()");
+    assert_eq!(first.get_source(), &SourcePath::NotebookCell { notebook: path.clone(), cell: 1 });
+    assert!(cache.display_path(&cells[0]).unwrap().ends_with("analysis.ipynb, cell [2]"));
+    assert_eq!(cache.fetch(&cells[1]).unwrap().text(), "z = 1");
+
+    // Spans in cells resolve to the JSON text, across escapes and list items
+    let json = SourcePath::Local(path.clone()).source_id();
+    let uper = cache.map_span(cells[0].with_range(18..22)).unwrap();
+    assert_eq!(uper.file, json);
+    assert_eq!(&NOTEBOOK[uper.start as usize..uper.end as usize], "uper");
+    let quote = cache.map_span(cells[0].with_range(4..5)).unwrap();
+    assert_eq!(&NOTEBOOK[quote.start as usize..quote.start as usize + 2], "\\\"");
+    let z = cache.map_span(cells[1].with_range(0..1)).unwrap();
+    assert_eq!(&NOTEBOOK[z.start as usize..z.end as usize], "z");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn invalid_notebooks() {
+    let path = write_temp("broken.ipynb", r#"{"cells": [{"cell_type": "code", "source": 1}]}"#);
+    let mut cache = SourceCache::default();
+    assert_eq!(cache.load_notebook(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,40 @@
+use super::*;
+use source_cache::{PathRemap, SharedSourceCache, SourcePath};
+
+#[test]
+fn relative_to_root() {
+    let remap = PathRemap::default().with_root("/work/app");
+    let local = |p: &str| SourcePath::Local(p.into());
+    assert_eq!(remap.display(&local("/work/app/src/lib.rs")), "src/lib.rs");
+    assert_eq!(remap.display(&local("/work/application/lib.rs")), local("/work/application/lib.rs").to_string());
+    assert_eq!(remap.display(&SourcePath::Snippet("repl".into())), "repl");
+}
+
+#[test]
+fn last_prefix_wins() {
+    let remap =
+        PathRemap::default().with_root("/work").with_prefix("/work/vendor", "vendor").with_prefix("/work/vendor/url", "$URL");
+    assert_eq!(remap.remap("/work/vendor/url/src/lib.rs".as_ref()), Some("$URL/src/lib.rs".into()));
+    assert_eq!(remap.remap("/work/vendor/serde".as_ref()), Some("vendor/serde".into()));
+    assert_eq!(remap.remap("/work/vendor".as_ref()), Some("vendor".into()));
+    assert_eq!(remap.remap("/work/src/main.rs".as_ref()), Some("src/main.rs".into()));
+    assert_eq!(remap.remap("/home/main.rs".as_ref()), None);
+}
+
+#[test]
+fn cache_display_path() {
+    let root = std::env::temp_dir();
+    let path = write_temp("remap.x", "let x = 1;");
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+    let mut cache = SourceCache::default();
+    let file = cache.load_local(&path).unwrap();
+    assert_eq!(cache.display_path(&file), Some(cache.source_path(&file).unwrap().to_string()));
+    cache.set_path_remap(PathRemap::default().with_root(&root));
+    assert_eq!(cache.display_path(&file), Some(name.clone()));
+    let shared = SharedSourceCache::from(cache);
+    assert_eq!(shared.display_path(&file), Some(name.clone()));
+    assert_eq!(shared.snapshot().get_path_remap().get_root(), Some(root.as_path()));
+    shared.set_path_remap(PathRemap::default().with_prefix(&root, "$TMP"));
+    assert_eq!(shared.display_path(&file), Some(format!("$TMP/{}", name)));
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,46 @@
+use super::*;
+use source_cache::SharedSourceCache;
+use std::sync::Arc;
+
+#[test]
+fn load_in_parallel() {
+    let paths: Vec<PathBuf> = (0..16).map(|i| write_temp(&format!("shared-{}.txt", i), &format!("file {}", i))).collect();
+    let cache = Arc::new(SharedSourceCache::default());
+    let files = cache.load_locals(&paths);
+    let snippets: Vec<_> = (0..4)
+        .map(|i| {
+            let cache = cache.clone();
+            std::thread::spawn(move || cache.load_text(format!("snippet {}", i), format!("snippet-{}", i)).unwrap())
+        })
+        .collect();
+    let snippets: Vec<_> = snippets.into_iter().map(|t| t.join().unwrap()).collect();
+
+    for (i, file) in files.into_iter().enumerate() {
+        assert_eq!(cache.fetch(&file.unwrap()).unwrap().text(), format!("file {}", i));
+    }
+    // Handles are rendered from other threads
+    let snapshot = cache.snapshot();
+    std::thread::scope(|scope| {
+        for (i, snippet) in snippets.iter().enumerate() {
+            let snapshot = &snapshot;
+            scope.spawn(move || assert_eq!(snapshot.fetch(snippet).unwrap().text(), format!("snippet {}", i)));
+        }
+    });
+    for path in paths {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn handles_outlive_replacement() {
+    let cache = SharedSourceCache::default();
+    let file = cache.load_text("old", "replaced.x").unwrap();
+    let old = cache.fetch(&file).unwrap();
+    assert_eq!(cache.load_text("new", "replaced.x").unwrap(), file);
+    assert_eq!(old.text(), "old");
+    assert_eq!(cache.fetch(&file).unwrap().text(), "new");
+
+    let mut single = SourceCache::default();
+    single.insert(old).unwrap();
+    assert_eq!(single.fetch(&file).unwrap().text(), "old");
+}
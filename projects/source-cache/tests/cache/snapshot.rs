@@ -0,0 +1,68 @@
+use super::*;
+use std::io::ErrorKind;
+
+#[test]
+fn deferred_sources_are_verified() {
+    let path = write_temp("deferred.x", "let x = 1;\n");
+    let hash = SourceText::from("let x = 1;\n").get_content_hash();
+    let mut cache = SourceCache::default();
+    let file = cache.load_deferred(&path, hash).unwrap();
+    assert_eq!(cache.fetch(&file).unwrap().text(), "let x = 1;\n");
+
+    let mut cache = SourceCache::default();
+    let file = cache.load_deferred(&path, hash).unwrap();
+    std::fs::write(&path, "let x = 2;\n").unwrap();
+    assert_eq!(cache.fetch(&file).unwrap_err().kind(), ErrorKind::InvalidData);
+    // Loading the file again replaces the deferred source
+    assert_eq!(cache.load_local(&path).unwrap(), file);
+    assert_eq!(cache.fetch(&file).unwrap().text(), "let x = 2;\n");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn snapshots_round_trip() {
+    use source_cache::{SnapshotMode, SourceSnapshot};
+    let path = write_temp("snapshot.x", "fn main() {\n    run();\n}\n");
+    let mut cache = SourceCache::default();
+    let local = cache.load_local(&path).unwrap();
+    let snippet = cache.load_text("1 + 2", "<repl>").unwrap();
+
+    let json = serde_json::to_string(&cache.to_snapshot(SnapshotMode::Contents)).unwrap();
+    std::fs::write(&path, "fn main() {}\n").unwrap();
+    let restored = SourceCache::from_snapshot(serde_json::from_str::<SourceSnapshot>(&json).unwrap()).unwrap();
+    assert_eq!(restored.fetch(&local).unwrap().get_line_text(1), Some("    run();"));
+    assert_eq!(restored.fetch(&snippet).unwrap().text(), "1 + 2");
+
+    // Hashes are only replayed while the file is unchanged
+    let json = serde_json::to_string(&cache.to_snapshot(SnapshotMode::Hashes)).unwrap();
+    assert!(!json.contains("run();"));
+    let restored = SourceCache::from_snapshot(serde_json::from_str::<SourceSnapshot>(&json).unwrap()).unwrap();
+    assert_eq!(restored.source_path(&local), cache.source_path(&local));
+    assert_eq!(restored.fetch(&local).unwrap_err().kind(), ErrorKind::InvalidData);
+    std::fs::write(&path, "fn main() {\n    run();\n}\n").unwrap();
+    let restored = SourceCache::from_snapshot(serde_json::from_str::<SourceSnapshot>(&json).unwrap()).unwrap();
+    assert_eq!(restored.fetch(&local).unwrap().get_line_count(), 3);
+    assert_eq!(restored.fetch(&snippet).unwrap().text(), "1 + 2");
+
+    let tampered = json.replace("1 + 2", "1 + 3");
+    assert!(SourceCache::from_snapshot(serde_json::from_str::<SourceSnapshot>(&tampered).unwrap()).is_err());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn bad_line_tables_are_ignored() {
+    use source_cache::{SnapshotMode, SourceSnapshot};
+    let mut cache = SourceCache::default();
+    let file = cache.load_text("é = 1\nb = 2", "<repl>").unwrap();
+    let json = serde_json::to_value(cache.to_snapshot(SnapshotMode::Contents)).unwrap();
+    // End the first line inside `é`, or at the wrong terminator, the text and its hash are untouched
+    for (field, value) in [("text", serde_json::json!({"start": 0, "end": 1})), ("ending", "Cr".into())] {
+        let mut json = json.clone();
+        json["sources"][0]["lines"][0][field] = value;
+        let restored = SourceCache::from_snapshot(serde_json::from_value::<SourceSnapshot>(json).unwrap()).unwrap();
+        assert_eq!(restored.fetch(&file).unwrap().get_line_text(0), Some("é = 1"));
+        assert_eq!(restored.fetch(&file).unwrap().get_line(0).unwrap().ending, source_cache::LineEnding::Lf);
+    }
+}
@@ -0,0 +1,83 @@
+use super::*;
+use source_cache::{SourcePath, VirtualFileSystem};
+use std::path::Path;
+
+#[test]
+fn buffers_shadow_files() {
+    let path = write_temp("vfs-buffer.x", "saved");
+    let mut cache = SourceCache::default();
+    let saved = cache.load_local(&path).unwrap();
+    let unsaved = cache.open_buffer(&path, "unsaved").unwrap();
+    assert_eq!(saved, unsaved);
+    assert_eq!(cache.fetch(&saved).unwrap().text(), "unsaved");
+    assert_eq!(cache.fetch(&saved).unwrap().get_modified(), None);
+    // Refreshing follows the buffer, not the file
+    assert!(cache.refresh().is_empty());
+    cache.get_vfs().open_buffer(&path, "edited");
+    assert_eq!(cache.refresh(), vec![saved]);
+    assert_eq!(cache.close_buffer(&path).unwrap(), saved);
+    assert_eq!(cache.fetch(&saved).unwrap().text(), "saved");
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn closing_unsaved_files() {
+    let mut cache = SourceCache::default();
+    let file = cache.open_buffer("untitled-1.x", "new file").unwrap();
+    assert_eq!(cache.source_path(&file), Some(&SourcePath::Local("untitled-1.x".into())));
+    assert!(cache.close_buffer("untitled-1.x").is_err());
+    assert!(cache.fetch(&file).is_err());
+}
+
+#[test]
+fn layers_are_stacked() {
+    let vendor = |path: &Path| match path.starts_with("vendor") {
+        true => Some(Ok(SourceText::from("vendored"))),
+        false => None,
+    };
+    let mut vfs = VirtualFileSystem::empty().with_layer(vendor);
+    assert_eq!(vfs.read("vendor/lib.x").unwrap().get_source(), &SourcePath::Local("vendor/lib.x".into()));
+    assert!(vfs.read("src/main.x").is_err());
+    vfs.push_layer(|_: &Path| Some(Ok(SourceText::from("patched"))));
+    assert_eq!(vfs.get_layer_count(), 2);
+    assert_eq!(vfs.read("vendor/lib.x").unwrap().text(), "patched");
+    vfs.open_buffer("vendor/lib.x", "open");
+    let mut cache = SourceCache::default();
+    cache.set_vfs(vfs.share());
+    let file = cache.load_local("vendor/lib.x").unwrap();
+    assert_eq!(cache.fetch(&file).unwrap().text(), "open");
+    assert!(vfs.close_buffer("vendor/lib.x").is_some());
+    assert!(cache.refresh_source(&file));
+    assert_eq!(cache.fetch(&file).unwrap().text(), "patched");
+}
+
+#[test]
+fn clones_copy_buffers() {
+    let mut original = SourceCache::default();
+    original.open_buffer("vfs-clone.x", "original").unwrap();
+    let mut clone = original.clone();
+    let file = clone.open_buffer("vfs-clone.x", "clone").unwrap();
+    assert_eq!(original.get_vfs().read("vfs-clone.x").unwrap().text(), "original");
+    assert_eq!(original.fetch(&file).unwrap().text(), "original");
+    assert_eq!(clone.fetch(&file).unwrap().text(), "clone");
+    // Only an explicit handle shares the buffers
+    let editor = original.get_vfs().share();
+    editor.open_buffer("vfs-clone.x", "edited");
+    assert_eq!(original.get_vfs().read("vfs-clone.x").unwrap().text(), "edited");
+    assert_eq!(clone.get_vfs().read("vfs-clone.x").unwrap().text(), "clone");
+}
+
+#[test]
+fn file_urls_see_buffers() {
+    let path = write_temp("vfs-url.x", "on disk");
+    let url = source_cache::Url::from_file_path(&path).unwrap();
+    let mut cache = SourceCache::default();
+    let file = cache.open_buffer(&path, "unsaved").unwrap();
+    assert_eq!(cache.load_remote(url.clone()).unwrap(), file);
+    assert_eq!(cache.fetch(&file).unwrap().text(), "unsaved");
+
+    let shared = source_cache::SharedSourceCache::from(cache);
+    assert_eq!(shared.load_remote(url).unwrap(), file);
+    assert_eq!(shared.fetch(&file).unwrap().text(), "unsaved");
+    std::fs::remove_file(path).unwrap();
+}
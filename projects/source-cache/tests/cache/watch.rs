@@ -0,0 +1,68 @@
+use super::*;
+use source_cache::SourceWatcher;
+use std::{
+    fs::File,
+    time::{Duration, SystemTime},
+};
+
+/// Replace the file at once, so the watcher never sees it half written
+fn touch(path: &PathBuf, text: &str, seconds: u64) {
+    let temp = path.with_extension("tmp");
+    std::fs::write(&temp, text).unwrap();
+    let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000 + seconds);
+    File::options().write(true).open(&temp).unwrap().set_modified(time).unwrap();
+    std::fs::rename(temp, path).unwrap();
+}
+
+#[test]
+fn refresh_reloads_changed_files() {
+    let path = write_temp("refresh.txt", "");
+    touch(&path, "let a = 1", 0);
+    let mut cache = SourceCache::default();
+    let file = cache.load_local(&path).unwrap();
+    assert!(cache.refresh().is_empty());
+
+    // Same content with a new time is not a change
+    touch(&path, "let a = 1", 1);
+    assert!(cache.refresh().is_empty());
+
+    touch(&path, "let a = 2", 2);
+    assert_eq!(cache.refresh(), vec![file]);
+    assert_eq!(cache.fetch(&file).unwrap().text(), "let a = 2");
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(cache.refresh(), vec![file]);
+    assert!(cache.is_stale(&file));
+    assert_eq!(cache.fetch(&file).unwrap().text(), "let a = 2");
+    assert!(cache.refresh().is_empty());
+
+    touch(&path, "let a = 3", 3);
+    assert_eq!(cache.refresh_sources(&[file]), vec![file]);
+    assert!(!cache.is_stale(&file));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn watcher_reports_changed_files() {
+    let path = write_temp("watch.txt", "");
+    let other = write_temp("watch-other.txt", "");
+    touch(&path, "a", 0);
+    touch(&other, "b", 0);
+    let mut cache = SourceCache::default();
+    let file = cache.load_local(&path).unwrap();
+    let watcher = SourceWatcher::new(&cache, Duration::from_millis(10));
+    watcher.watch(&other);
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(watcher.changed().is_empty());
+
+    touch(&path, "c", 1);
+    assert_eq!(watcher.wait(Duration::from_secs(5)), vec![file]);
+    assert_eq!(cache.refresh_sources(&[file]), vec![file]);
+
+    touch(&other, "d", 1);
+    let other_file = cache.load_local(&other).unwrap();
+    assert_eq!(watcher.wait(Duration::from_secs(5)), vec![other_file]);
+    drop(watcher);
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(other).unwrap();
+}
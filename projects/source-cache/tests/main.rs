@@ -1,3 +1,6 @@
+mod cache;
+mod text;
+
 #[test]
 fn ready() {
     println!("it works!")
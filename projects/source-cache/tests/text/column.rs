@@ -0,0 +1,36 @@
+use super::*;
+
+#[test]
+fn tabs_and_wide_characters() {
+    let text = SourceText::from("\tlet 名前 = 1;  \nx\ty");
+    let line = text.get_line(0).unwrap();
+    // Tab, `let `, two wide characters
+    assert_eq!(line.get_visual_column(&text, 0, 4), 0);
+    assert_eq!(line.get_visual_column(&text, 1, 4), 4);
+    assert_eq!(line.get_visual_column(&text, 5, 4), 8);
+    assert_eq!(line.get_visual_column(&text, 6, 4), 8);
+    assert_eq!(line.get_visual_column(&text, 8, 4), 10);
+    assert_eq!(line.get_visual_column(&text, 11, 4), 12);
+    // Past the visible text every byte is a column
+    assert_eq!(line.get_visual_column(&text, 18, 4), 19);
+    assert_eq!(text.get_visual_position(19, 4), Some((1, 0)));
+    assert_eq!(text.get_visual_position(21, 8), Some((1, 8)));
+    assert_eq!(visual_width('\t', 5, 8), 3);
+}
+
+#[test]
+fn visual_to_byte_columns() {
+    let text = SourceText::from("\tlet 名前 = 1;\nx\ty");
+    let line = text.get_line(0).unwrap();
+    assert_eq!(line.get_byte_column(&text, 2, 4), 0);
+    assert_eq!(line.get_byte_column(&text, 4, 4), 1);
+    assert_eq!(line.get_byte_column(&text, 9, 4), 5);
+    assert_eq!(line.get_byte_column(&text, 10, 4), 8);
+    assert_eq!(line.get_byte_column(&text, 100, 4), line.length);
+    for column in [0, 1, 5, 8, 11, 12] {
+        let visual = line.get_visual_column(&text, column, 4);
+        assert_eq!(line.get_byte_column(&text, visual, 4), column);
+    }
+    assert_eq!(text.get_visual_offset(1, 3, 4), Some(18));
+    assert_eq!(text.get_visual_offset(2, 0, 4), None);
+}
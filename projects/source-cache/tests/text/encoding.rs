@@ -0,0 +1,67 @@
+use super::*;
+use source_cache::{FileSystem, SourceCache, VirtualFileSystem};
+
+fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+    let bom: &[u8] = if big_endian { &[0xFE, 0xFF] } else { &[0xFF, 0xFE] };
+    let units = text.encode_utf16().flat_map(|u| if big_endian { u.to_be_bytes() } else { u.to_le_bytes() });
+    bom.iter().copied().chain(units).collect()
+}
+
+#[test]
+fn detects_byte_order_marks() {
+    let decoder = TextDecoder::default();
+    for big_endian in [false, true] {
+        let source = decoder.decode(utf16("a = 'é😀';\nb", big_endian)).unwrap();
+        assert_eq!(source.text(), "a = 'é😀';\nb");
+        assert_eq!(source.get_encoding(), if big_endian { TextEncoding::Utf16Be } else { TextEncoding::Utf16Le });
+        // BOM, then 5 units before `é`, which is 1 unit, then a surrogate pair
+        assert_eq!(source.get_text_offset(2 + 10), 5);
+        assert_eq!(source.get_text_offset(2 + 12), 7);
+        assert_eq!(source.get_text_offset(2 + 15), 7);
+        assert_eq!(source.get_text_offset(2 + 16), 11);
+        assert_eq!(source.get_file_offset(7), 14);
+        assert_eq!(source.get_file_offset(0), 2);
+        assert_eq!(source.get_line_count(), 2);
+    }
+    let source = decoder.decode(b"\xEF\xBB\xBFkey = 1".to_vec()).unwrap();
+    assert_eq!((source.text(), source.get_encoding()), ("key = 1", TextEncoding::Utf8));
+    assert_eq!(source.get_text_offset(9), 6);
+    assert_eq!(source.get_file_offset(6), 9);
+}
+
+#[test]
+fn legacy_and_invalid_input() {
+    let latin1 = TextDecoder::default().with_encoding(TextEncoding::Latin1);
+    let source = latin1.decode(b"caf\xE9 = 1".to_vec()).unwrap();
+    assert_eq!(source.text(), "café = 1");
+    assert_eq!(source.get_text_offset(5), 6);
+
+    assert!(TextDecoder::default().decode(b"a\xFFb".to_vec()).is_err());
+    let lossy = TextDecoder::default().with_lossy(true);
+    let source = lossy.decode(b"a\xFFb\xE2\x82".to_vec()).unwrap();
+    assert_eq!(source.text(), "a\u{FFFD}b\u{FFFD}");
+    assert_eq!(source.get_replacements(), &[1, 5]);
+    // Spans computed on the file bytes cover the replacement characters
+    let file = source.source_id();
+    assert_eq!(source.get_text_span(file.with_range(2..3)), file.with_range(4..5));
+    assert_eq!(source.get_text_span(file.with_range(3..4)), file.with_range(5..8));
+    assert!(TextDecoder::default().decode(utf16("x", false)[..3].to_vec()).is_err());
+    assert_eq!(lossy.decode(utf16("x", false)[..3].to_vec()).unwrap().text(), "\u{FFFD}");
+}
+
+#[test]
+fn loads_encoded_files() {
+    let path = std::env::temp_dir().join(format!("source-cache-{}-utf16.ini", std::process::id()));
+    std::fs::write(&path, utf16("[section]\nkey = value\n", false)).unwrap();
+    let mut cache = SourceCache::default();
+    let file = cache.load_local(&path).unwrap();
+    assert_eq!(cache.fetch(&file).unwrap().get_line_text(1), Some("key = value"));
+
+    std::fs::write(&path, b"name = J\xF6rg\n").unwrap();
+    assert!(cache.load_local(&path).is_err());
+    let latin1 = TextDecoder::default().with_encoding(TextEncoding::Latin1);
+    cache.set_vfs(VirtualFileSystem::empty().with_layer(FileSystem::new(latin1)));
+    let file = cache.load_local(&path).unwrap();
+    assert_eq!(cache.fetch(&file).unwrap().get_line_text(0), Some("name = Jörg"));
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,41 @@
+use super::*;
+
+#[test]
+fn every_terminator() {
+    let text = SourceText::from("a\nb\r\nc\rd\x0Be\x0Cf\u{85}g\u{2028}h\u{2029}i");
+    let endings: Vec<_> = text.lines().map(|l| l.ending).collect();
+    assert_eq!(
+        endings,
+        vec![
+            LineEnding::Lf,
+            LineEnding::CrLf,
+            LineEnding::Cr,
+            LineEnding::VerticalTab,
+            LineEnding::FormFeed,
+            LineEnding::NextLine,
+            LineEnding::LineSeparator,
+            LineEnding::ParagraphSeparator,
+            LineEnding::None,
+        ]
+    );
+    for line in text.lines() {
+        assert_eq!(&text.text()[line.ending_range().start as usize..line.range().end as usize], line.ending.as_str());
+    }
+}
+
+#[test]
+fn mixed_endings() {
+    let text = SourceText::from("one  \r\ntwo\nthree\r\nfour\n\n");
+    assert_eq!(text.get_line_ending(), Some(LineEnding::Lf));
+    let mixed: Vec<_> = text.get_mixed_endings().iter().map(|s| s.get_range()).collect();
+    assert_eq!(mixed, vec![5..7, 16..18]);
+    assert_eq!(SourceText::from("a\r\nb\n").get_line_ending(), Some(LineEnding::CrLf));
+    assert_eq!(SourceText::from("no terminator").get_line_ending(), None);
+    assert!(SourceText::from("a\nb\n").get_mixed_endings().is_empty());
+    // Page breaks and separators are not newlines
+    let text = SourceText::from("a\nb\x0Cc\u{2028}d\n");
+    assert_eq!(text.get_line_count(), 4);
+    assert!(text.get_mixed_endings().is_empty());
+    assert_eq!(SourceText::from("a\x0Cb\x0Cc\r\n").get_line_ending(), Some(LineEnding::CrLf));
+    assert_eq!(SourceText::from("a\x0Cb").get_line_ending(), None);
+}
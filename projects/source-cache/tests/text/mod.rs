@@ -0,0 +1,6 @@
+use source_cache::{visual_width, LineEnding, SourceSpan, SourceText, TextDecoder, TextEncoding};
+
+mod column;
+mod encoding;
+mod ending;
+mod span;
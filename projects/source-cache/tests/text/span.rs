@@ -0,0 +1,59 @@
+use super::*;
+
+#[test]
+fn set_operations() {
+    let file = SourceText::snippet("", "a").source_id();
+    let other = SourceText::snippet("", "b").source_id();
+    let a = file.with_range(2..6);
+    let b = file.with_range(4..9);
+    assert_eq!(a.join(&b), Some(file.with_range(2..9)));
+    assert_eq!(a.intersect(&b), Some(file.with_range(4..6)));
+    assert_eq!(a.intersect(&file.with_range(6..8)), Some(file.with_range(6..6)));
+    assert_eq!(a.intersect(&file.with_range(7..8)), None);
+    assert!(a.overlaps(&b));
+    assert!(!a.overlaps(&file.with_range(6..8)));
+    assert!(a.overlaps(&file.with_range(3..3)));
+    assert!(a.contains_span(&file.with_range(3..6)));
+    assert!(!a.contains_span(&b));
+    // Spans of different files never combine
+    let c = other.with_range(2..6);
+    assert_eq!(a.join(&c), None);
+    assert_eq!(a.intersect(&c), None);
+    assert!(!a.overlaps(&c));
+    assert!(!a.contains_span(&c));
+}
+
+#[test]
+fn resizing() {
+    let file = SourceText::snippet("", "a").source_id();
+    let span = file.with_range(4..10);
+    assert_eq!(span.shrink(1, 2), file.with_range(5..8));
+    assert_eq!(span.shrink(5, 5), file.with_range(9..9));
+    assert_eq!(span.expand(10, 1), file.with_range(0..11));
+    assert_eq!(span.shift(3), Some(file.with_range(7..13)));
+    assert_eq!(span.shift(-4), Some(file.with_range(0..6)));
+    assert_eq!(span.shift(-5), None);
+    assert_eq!(span.shift(u32::MAX as i64), None);
+}
+
+#[test]
+fn against_text() {
+    let source = SourceText::snippet("fn main() {\n    call( x );\n}\n", "main.rs");
+    let file = source.source_id();
+    let args = SourceSpan::from_line_column(&source, (1, 9), (1, 12)).unwrap();
+    assert_eq!(args, file.with_range(21..24));
+    assert_eq!(args.text(&source), Some(" x "));
+    assert_eq!(args.trim(&source), Some(file.with_range(22..23)));
+    assert_eq!(file.with_range(11..16).trim(&source), Some(file.with_range(16..16)));
+    assert_eq!(SourceSpan::from_line_column(&source, (1, 9), (0, 1)), None);
+    assert_eq!(SourceSpan::from_line_column(&source, (0, 20), (1, 1)), None);
+    assert_eq!(SourceSpan::from_line_column(&source, (5, 0), (5, 0)), None);
+
+    let body = file.with_range(10..28);
+    let lines: Vec<_> = body.split_lines(&source).unwrap().into_iter().map(|s| s.text(&source).unwrap()).collect();
+    assert_eq!(lines, vec!["{\n", "    call( x );\n", "}"]);
+    assert_eq!(file.with_range(3..3).split_lines(&source), Some(vec![file.with_range(3..3)]));
+    let elsewhere = SourceText::snippet("", "other.rs");
+    assert_eq!(body.split_lines(&elsewhere), None);
+    assert_eq!(body.trim(&elsewhere), None);
+}